@@ -0,0 +1,56 @@
+/// A set of child keys, able to find the closest present key in a few word operations.
+#[derive(Clone, Default)]
+pub(crate) struct Bitmap([u64; 4]);
+
+impl Bitmap {
+    pub fn insert(&mut self, key: u8) {
+        self.0[key as usize / 64] |= 1 << (key % 64);
+    }
+
+    pub fn remove(&mut self, key: u8) {
+        self.0[key as usize / 64] &= !(1 << (key % 64));
+    }
+
+    /// Find the smallest key after the given one.
+    pub fn next(&self, after: Option<u8>) -> Option<u8> {
+        let start = after.map_or(0, |after| after as usize + 1);
+        if start == 256 {
+            return None;
+        }
+
+        let mut word = start / 64;
+        let mut bits = self.0[word] & (!0 << (start % 64));
+        loop {
+            if bits != 0 {
+                return Some((word * 64 + bits.trailing_zeros() as usize) as u8);
+            }
+            word += 1;
+            if word == self.0.len() {
+                return None;
+            }
+            bits = self.0[word];
+        }
+    }
+
+    /// Find the largest key before the given one.
+    pub fn prev(&self, before: Option<u8>) -> Option<u8> {
+        let end = before.map_or(256, |before| before as usize);
+        if end == 0 {
+            return None;
+        }
+
+        let last = end - 1;
+        let mut word = last / 64;
+        let mut bits = self.0[word] & (!0 >> (63 - last % 64));
+        loop {
+            if bits != 0 {
+                return Some((word * 64 + 63 - bits.leading_zeros() as usize) as u8);
+            }
+            if word == 0 {
+                return None;
+            }
+            word -= 1;
+            bits = self.0[word];
+        }
+    }
+}
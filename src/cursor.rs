@@ -0,0 +1,195 @@
+use std::marker::PhantomData;
+use std::ops::Bound;
+
+use super::{
+    iter::{
+        ChildRef,
+        Leaves,
+        Walk,
+    },
+    Child,
+    KeyContainsTerminator,
+    Summary,
+};
+
+/// A position in a trie, which can move to the entries next to it.
+///
+/// Besides the entries, the cursor can point at a "ghost" position past the last entry and before
+/// the first one, which is where it starts.
+pub struct Cursor<'a, 'b, T, S = ()> {
+    leaves: Leaves<'a, 'b, T, S>,
+    current: Option<&'b T>,
+    term: u8,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Cursor<'a, 'b, T, S> {
+    pub(crate) fn new(leaves: Leaves<'a, 'b, T, S>, term: u8) -> Self {
+        Cursor { leaves, current: None, term }
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.current.map(|_| self.leaves.key())
+    }
+
+    pub fn value(&self) -> Option<&'b T> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<(&[u8], &'b T)> {
+        self.current.map(|value| (self.leaves.key(), value))
+    }
+
+    /// Move to the first entry with a key greater than or equal to the given one.
+    pub fn seek(&mut self, key: &[u8]) -> Result<Option<(&[u8], &'b T)>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.seek_impl(Bound::Included(key), true))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn seek_unchecked(&mut self, key: &[u8]) -> Option<(&[u8], &'b T)> {
+        self.seek_impl(Bound::Included(key), true)
+    }
+
+    pub fn seek_first(&mut self) -> Option<(&[u8], &'b T)> {
+        self.seek_impl(Bound::Unbounded, true)
+    }
+
+    pub fn seek_last(&mut self) -> Option<(&[u8], &'b T)> {
+        self.seek_impl(Bound::Unbounded, false)
+    }
+
+    pub fn move_next(&mut self) -> Option<(&[u8], &'b T)> {
+        self.step(true)
+    }
+
+    pub fn move_prev(&mut self) -> Option<(&[u8], &'b T)> {
+        self.step(false)
+    }
+
+    fn seek_impl(&mut self, bound: Bound<&[u8]>, forward: bool) -> Option<(&[u8], &'b T)> {
+        self.current = self.leaves.seek(bound, forward);
+        self.current()
+    }
+
+    fn step(&mut self, forward: bool) -> Option<(&[u8], &'b T)> {
+        if self.current.is_none() {
+            return self.seek_impl(Bound::Unbounded, forward);
+        }
+        self.current = self.leaves.step(forward);
+        self.current()
+    }
+}
+
+/// A child reached through the trie's mutable borrow, which `CursorMut` holds on to for as long
+/// as it can reach any.
+pub(crate) struct ChildMut<'a, T>(*mut Child<'a, T, ()>);
+
+impl<'a, T> Clone for ChildMut<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ChildMut<'a, T> {}
+
+impl<'a, T> ChildRef<'a, T, ()> for ChildMut<'a, T> {
+    type Value = *mut T;
+
+    fn get(&self) -> &Child<'a, T, ()> {
+        unsafe { &*self.0 }
+    }
+
+    fn child(self, key: u8) -> Option<Self> {
+        let node = unsafe { &mut *self.0 }.as_node_mut()?;
+        node.inner.find_child_mut(key).map(|child| ChildMut(child))
+    }
+
+    fn value(self) -> *mut T {
+        match unsafe { &mut *self.0 } {
+            Child::Leaf(_, value) => value,
+            Child::Node(_)        => unreachable!(),
+        }
+    }
+}
+
+/// A position in a trie, through which the current entry can be modified.
+pub struct CursorMut<'a, 'b, T> {
+    leaves: Walk<'a, ChildMut<'a, T>, T, ()>,
+    current: Option<*mut T>,
+    term: u8,
+    trie: PhantomData<&'b mut Option<Child<'a, T, ()>>>,
+}
+
+impl<'a, 'b, T> CursorMut<'a, 'b, T> {
+    pub(crate) fn new(root: &'b mut Option<Child<'a, T, ()>>, term: u8) -> Self {
+        let root = root.as_mut().map(|root| ChildMut(root));
+        CursorMut { leaves: Walk::new(root, Vec::new(), term), current: None, term, trie: PhantomData }
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.current.map(|_| self.leaves.key())
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        self.current.map(|value| unsafe { &*value })
+    }
+
+    pub fn value_mut(&mut self) -> Option<&mut T> {
+        self.current.map(|value| unsafe { &mut *value })
+    }
+
+    pub fn current(&mut self) -> Option<(&[u8], &mut T)> {
+        let value = self.current?;
+        Some((self.leaves.key(), unsafe { &mut *value }))
+    }
+
+    /// Move to the first entry with a key greater than or equal to the given one.
+    pub fn seek(&mut self, key: &[u8]) -> Result<Option<(&[u8], &mut T)>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.seek_impl(Bound::Included(key), true))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn seek_unchecked(&mut self, key: &[u8]) -> Option<(&[u8], &mut T)> {
+        self.seek_impl(Bound::Included(key), true)
+    }
+
+    pub fn seek_first(&mut self) -> Option<(&[u8], &mut T)> {
+        self.seek_impl(Bound::Unbounded, true)
+    }
+
+    pub fn seek_last(&mut self) -> Option<(&[u8], &mut T)> {
+        self.seek_impl(Bound::Unbounded, false)
+    }
+
+    pub fn move_next(&mut self) -> Option<(&[u8], &mut T)> {
+        self.step(true)
+    }
+
+    pub fn move_prev(&mut self) -> Option<(&[u8], &mut T)> {
+        self.step(false)
+    }
+
+    fn seek_impl(&mut self, bound: Bound<&[u8]>, forward: bool) -> Option<(&[u8], &mut T)> {
+        self.current = self.leaves.seek(bound, forward);
+        self.current()
+    }
+
+    fn step(&mut self, forward: bool) -> Option<(&[u8], &mut T)> {
+        if self.current.is_none() {
+            return self.seek_impl(Bound::Unbounded, forward);
+        }
+        self.current = self.leaves.step(forward);
+        self.current()
+    }
+}
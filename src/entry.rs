@@ -0,0 +1,277 @@
+use super::{
+    branch,
+    Child,
+};
+
+pub enum Entry<'a, 'b, T> {
+    Vacant(VacantEntry<'a, 'b, T>),
+    Occupied(OccupiedEntry<'a, 'b, T>),
+}
+
+pub struct VacantEntry<'a, 'b, T> {
+    key: Vec<u8>,
+    depth: usize,
+    slot: Slot<'a, 'b, T>,
+    len: &'b mut usize,
+    /// Entry counts of the nodes above the slot.
+    #[cfg(feature = "counts")]
+    counts: Vec<&'b mut usize>,
+    term: u8,
+}
+
+/// Where a vacant entry's key diverges from the keys already in the trie, `depth` bytes in.
+enum Slot<'a, 'b, T> {
+    Root(&'b mut Option<Child<'a, T, ()>>),
+    Child(&'b mut Child<'a, T, ()>),
+}
+
+pub struct OccupiedEntry<'a, 'b, T> {
+    key: Vec<u8>,
+    position: Position<'a, 'b, T>,
+    len: &'b mut usize,
+    /// Entry counts of the nodes above the parent of the entry's leaf.
+    #[cfg(feature = "counts")]
+    counts: Vec<&'b mut usize>,
+    term: u8,
+}
+
+/// A child in the trie, held through its parent so that it can be taken out of it.
+enum Position<'a, 'b, T> {
+    Root(&'b mut Option<Child<'a, T, ()>>),
+    /// The parent node's child, and the key of the child in it.
+    Child(&'b mut Child<'a, T, ()>, u8),
+}
+
+impl<'a, 'b, T> Position<'a, 'b, T> {
+    fn child(&self) -> &Child<'a, T, ()> {
+        match self {
+            Position::Root(root)        => root.as_ref().unwrap(),
+            Position::Child(parent, key) => match parent {
+                Child::Node(node) => node.find_child(*key).unwrap(),
+                Child::Leaf(..)   => unreachable!(),
+            },
+        }
+    }
+
+    fn child_mut(&mut self) -> &mut Child<'a, T, ()> {
+        match self {
+            Position::Root(root)        => root.as_mut().unwrap(),
+            Position::Child(parent, key) => parent.as_node_mut().unwrap().find_child_mut(*key).unwrap(),
+        }
+    }
+
+    /// Take the child out of its parent, adding the parent's entry count to `counts`.
+    fn into_child(self, #[cfg(feature = "counts")] counts: &mut Vec<&'b mut usize>) -> &'b mut Child<'a, T, ()> {
+        match self {
+            Position::Root(root)        => root.as_mut().unwrap(),
+            Position::Child(parent, key) => {
+                let node = parent.as_node_mut().unwrap();
+                #[cfg(feature = "counts")]
+                counts.push(&mut node.count);
+                node.inner.find_child_mut(key).unwrap()
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T> Entry<'a, 'b, T> {
+    pub(crate) fn new(root: &'b mut Option<Child<'a, T, ()>>, len: &'b mut usize, key: &[u8], term: u8) -> Self {
+        let key = key.to_vec();
+        let mut depth = 0;
+        #[cfg(feature = "counts")]
+        let mut counts = Vec::new();
+
+        if root.is_none() {
+            let slot = Slot::Root(root);
+            return Entry::Vacant(VacantEntry {
+                key,
+                depth,
+                slot,
+                len,
+                #[cfg(feature = "counts")]
+                counts,
+                term,
+            });
+        }
+
+        let mut position = Position::Root(root);
+        loop {
+            let child = position.child();
+            let diverges = match child {
+                Child::Leaf(suffix, _) => suffix[..] != key[depth..],
+                Child::Node(node)      => match key[depth..].strip_prefix(&node.prefix[..]) {
+                    Some(rest) => node.find_child(branch(rest, term).0).is_none(),
+                    None       => true,
+                },
+            };
+
+            if diverges {
+                #[cfg(feature = "counts")]
+                let child = position.into_child(&mut counts);
+                #[cfg(not(feature = "counts"))]
+                let child = position.into_child();
+                let slot = Slot::Child(child);
+                return Entry::Vacant(VacantEntry {
+                    key,
+                    depth,
+                    slot,
+                    len,
+                    #[cfg(feature = "counts")]
+                    counts,
+                    term,
+                });
+            }
+
+            let byte = match child {
+                Child::Leaf(..) => {
+                    return Entry::Occupied(OccupiedEntry {
+                        key,
+                        position,
+                        len,
+                        #[cfg(feature = "counts")]
+                        counts,
+                        term,
+                    });
+                }
+                Child::Node(node) => {
+                    depth += node.prefix.len();
+                    let (byte, _) = branch(&key[depth..], term);
+                    if byte != term {
+                        depth += 1;
+                    }
+                    byte
+                }
+            };
+            #[cfg(feature = "counts")]
+            let parent = position.into_child(&mut counts);
+            #[cfg(not(feature = "counts"))]
+            let parent = position.into_child();
+            position = Position::Child(parent, byte);
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Entry::Vacant(entry)   => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: T) -> &'b mut T {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'b mut T {
+        match self {
+            Entry::Vacant(entry)   => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&[u8]) -> T>(self, default: F) -> &'b mut T {
+        match self {
+            Entry::Vacant(entry)   => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_default(self) -> &'b mut T
+        where T: Default
+    {
+        self.or_insert_with(T::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, 'b, T> VacantEntry<'a, 'b, T> {
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn into_key(self) -> Vec<u8> {
+        self.key
+    }
+
+    pub fn insert(self, value: T) -> &'b mut T {
+        *self.len += 1;
+        #[cfg(feature = "counts")]
+        for count in self.counts {
+            *count += 1;
+        }
+        let rest = &self.key[self.depth..];
+        match self.slot {
+            Slot::Root(root)   => match root.insert(Child::Leaf(rest.to_vec(), value)) {
+                Child::Leaf(_, value) => value,
+                Child::Node(_)        => unreachable!(),
+            },
+            Slot::Child(child) => child.insert_new(rest, value, self.term),
+        }
+    }
+}
+
+impl<'a, 'b, T> OccupiedEntry<'a, 'b, T> {
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn get(&self) -> &T {
+        match self.position.child() {
+            Child::Leaf(_, value) => value,
+            Child::Node(_)        => unreachable!(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        match self.position.child_mut() {
+            Child::Leaf(_, value) => value,
+            Child::Node(_)        => unreachable!(),
+        }
+    }
+
+    pub fn into_mut(self) -> &'b mut T {
+        let child = match self.position {
+            Position::Root(root)        => root.as_mut().unwrap(),
+            Position::Child(parent, key) => parent.as_node_mut().unwrap().find_child_mut(key).unwrap(),
+        };
+        match child {
+            Child::Leaf(_, value) => value,
+            Child::Node(_)        => unreachable!(),
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> T {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (Vec<u8>, T) {
+        *self.len -= 1;
+        #[cfg(feature = "counts")]
+        for count in self.counts {
+            *count -= 1;
+        }
+        let value = match self.position {
+            Position::Root(root)        => root.take().and_then(Child::into_leaf),
+            Position::Child(parent, key) => {
+                let node = parent.as_node_mut().unwrap();
+                let value = node.remove_child(key).and_then(Child::into_leaf);
+                node.shrink(1);
+                parent.compress(self.term);
+                value
+            }
+        };
+        (self.key, value.unwrap())
+    }
+}
@@ -0,0 +1,548 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::Bound;
+
+use super::{
+    branch,
+    common_prefix_len,
+    Child,
+    Summary,
+};
+
+/// A handle on a child reached during a traversal, through which the children below it are
+/// reached in turn.
+pub(crate) trait ChildRef<'a, T, S>: Copy {
+    type Value;
+
+    fn get(&self) -> &Child<'a, T, S>;
+
+    /// Reach the child with the given key of the node this handle is on.
+    fn child(self, key: u8) -> Option<Self>;
+
+    /// Reach the value of the leaf this handle is on.
+    fn value(self) -> Self::Value;
+}
+
+impl<'a, 'b, T, S> ChildRef<'a, T, S> for &'b Child<'a, T, S> {
+    type Value = &'b T;
+
+    fn get(&self) -> &Child<'a, T, S> {
+        self
+    }
+
+    fn child(self, key: u8) -> Option<Self> {
+        match self {
+            Child::Node(node) => node.inner.find_child(key),
+            Child::Leaf(..)   => None,
+        }
+    }
+
+    fn value(self) -> &'b T {
+        match self {
+            Child::Leaf(_, value) => value,
+            Child::Node(_)        => unreachable!(),
+        }
+    }
+}
+
+/// Position of a traversal in an inner node: the key of the child being visited, and the length
+/// of the key leading to that child.
+struct Frame<C> {
+    node: C,
+    key: u8,
+    depth: usize,
+}
+
+/// Traversal reaching the children through shared references.
+pub(crate) type Leaves<'a, 'b, T, S> = Walk<'a, &'b Child<'a, T, S>, T, S>;
+
+/// Depth-first traversal of the leaves of a subtree, in either key order.
+pub(crate) struct Walk<'a, C, T, S> {
+    root: Option<C>,
+    stack: Vec<Frame<C>>,
+    key: Vec<u8>,
+    depth: usize,
+    /// Length of the start of the keys left out of them.
+    skip: usize,
+    term: u8,
+    marker: PhantomData<fn() -> Child<'a, T, S>>,
+}
+
+impl<'a, C: ChildRef<'a, T, S>, T, S: Summary<T>> Walk<'a, C, T, S> {
+    pub(crate) fn new(root: Option<C>, prefix: Vec<u8>, term: u8) -> Self {
+        let depth = prefix.len();
+        Walk { root, stack: Vec::new(), key: prefix, depth, skip: 0, term, marker: PhantomData }
+    }
+
+    /// Leave the given number of bytes out of the start of the keys, which all leaves share.
+    pub(crate) fn skip(mut self, len: usize) -> Self {
+        self.skip = len;
+        self
+    }
+
+    pub(crate) fn key(&self) -> &[u8] {
+        &self.key[self.skip..]
+    }
+
+    /// Move to the first leaf past the bound (relative to the root) in the given direction.
+    pub(crate) fn seek(&mut self, bound: Bound<&[u8]>, forward: bool) -> Option<C::Value> {
+        self.stack.clear();
+        self.key.truncate(self.depth);
+
+        let (mut probe, inclusive) = match bound {
+            Bound::Unbounded       => return self.root.map(|root| self.descend(root, forward)),
+            Bound::Included(probe) => (probe, true),
+            Bound::Excluded(probe) => (probe, false),
+        };
+
+        let mut child = self.root?;
+        loop {
+            match child.get() {
+                Child::Leaf(suffix, _) => {
+                    if !is_past(suffix[..].cmp(probe), inclusive, forward) {
+                        return self.step(forward);
+                    }
+                    self.key.extend_from_slice(suffix);
+                    return Some(child.value());
+                }
+                Child::Node(node) => {
+                    let matched = common_prefix_len(&node.prefix, probe);
+                    if matched < node.prefix.len() {
+                        // All keys of the subtree compare to the probe the same way its prefix does
+                        let ordering = match probe.get(matched) {
+                            Some(byte) => node.prefix[matched].cmp(byte),
+                            None       => Ordering::Greater,
+                        };
+                        if !is_past(ordering, false, forward) {
+                            return self.step(forward);
+                        }
+                        return Some(self.descend(child, forward));
+                    }
+
+                    self.key.extend_from_slice(&node.prefix);
+                    let (key, rest) = branch(&probe[matched..], self.term);
+                    self.stack.push(Frame { node: child, key, depth: self.key.len() });
+                    match child.child(key) {
+                        Some(next) => {
+                            if key != self.term {
+                                self.key.push(key);
+                            }
+                            child = next;
+                            probe = rest;
+                        }
+                        None => return self.step(forward),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move to the leaf following the current one in the given direction.
+    pub(crate) fn step(&mut self, forward: bool) -> Option<C::Value> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            let node = match frame.node.get() {
+                Child::Node(node) => node,
+                Child::Leaf(..)   => unreachable!(),
+            };
+            let sibling = if forward {
+                node.next_child(Some(frame.key), self.term)
+            } else {
+                node.prev_child(Some(frame.key), self.term)
+            };
+
+            match sibling {
+                Some((key, _)) => {
+                    frame.key = key;
+                    let child = frame.node.child(key).unwrap();
+                    self.key.truncate(frame.depth);
+                    if key != self.term {
+                        self.key.push(key);
+                    }
+                    return Some(self.descend(child, forward));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Move to the first leaf of a subtree in the given direction.
+    fn descend(&mut self, mut child: C, forward: bool) -> C::Value {
+        loop {
+            let key = match child.get() {
+                Child::Leaf(suffix, _) => {
+                    self.key.extend_from_slice(suffix);
+                    return child.value();
+                }
+                Child::Node(node) => {
+                    self.key.extend_from_slice(&node.prefix);
+                    let (key, _) = if forward {
+                        node.next_child(None, self.term).unwrap()
+                    } else {
+                        node.prev_child(None, self.term).unwrap()
+                    };
+                    key
+                }
+            };
+            self.stack.push(Frame { node: child, key, depth: self.key.len() });
+            if key != self.term {
+                self.key.push(key);
+            }
+            child = child.child(key).unwrap();
+        }
+    }
+}
+
+/// Whether a key comparing to a bound this way lies past it, in the given direction.
+fn is_past(ordering: Ordering, inclusive: bool, forward: bool) -> bool {
+    match ordering {
+        Ordering::Equal   => inclusive,
+        Ordering::Greater => forward,
+        Ordering::Less    => !forward,
+    }
+}
+
+/// Owned start and end bounds of a traversal.
+type Bounds = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Leaves between two bounds, consumed from either end.
+pub(crate) struct Span<'a, 'b, T, S> {
+    front: Leaves<'a, 'b, T, S>,
+    back: Leaves<'a, 'b, T, S>,
+    bounds: Option<Bounds>,
+    ends: Option<(&'b T, &'b T)>,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Span<'a, 'b, T, S> {
+    pub(crate) fn new(leaves: Leaves<'a, 'b, T, S>, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Self {
+        let back = Leaves::new(leaves.root, leaves.key.clone(), leaves.term).skip(leaves.skip);
+        Span { front: leaves, back, bounds: Some((start, end)), ends: None }
+    }
+
+    fn seek_ends(&mut self) {
+        if let Some((start, end)) = self.bounds.take() {
+            let front = self.front.seek(as_ref(&start), true);
+            let back = self.back.seek(as_ref(&end), false);
+            self.ends = match (front, back) {
+                (Some(front), Some(back)) if self.front.key() <= self.back.key() => Some((front, back)),
+                _                                                                => None,
+            };
+        }
+    }
+
+    pub(crate) fn next_front(&mut self) -> Option<(Vec<u8>, &'b T)> {
+        self.seek_ends();
+        let (front, back) = self.ends?;
+        let key = self.front.key().to_vec();
+        self.ends = if self.front.key() == self.back.key() {
+            None
+        } else {
+            self.front.step(true).map(|front| (front, back))
+        };
+        Some((key, front))
+    }
+
+    pub(crate) fn next_back(&mut self) -> Option<(Vec<u8>, &'b T)> {
+        self.seek_ends();
+        let (front, back) = self.ends?;
+        let key = self.back.key().to_vec();
+        self.ends = if self.front.key() == self.back.key() {
+            None
+        } else {
+            self.back.step(false).map(|back| (front, back))
+        };
+        Some((key, back))
+    }
+}
+
+fn as_ref(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded     => Bound::Unbounded,
+    }
+}
+
+pub struct Iter<'a, 'b, T, S = ()> {
+    span: Span<'a, 'b, T, S>,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iter<'a, 'b, T, S> {
+    pub(crate) fn new(leaves: Leaves<'a, 'b, T, S>) -> Self {
+        Iter { span: Span::new(leaves, Bound::Unbounded, Bound::Unbounded) }
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iterator for Iter<'a, 'b, T, S> {
+    type Item = (Vec<u8>, &'b T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.span.next_front()
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> DoubleEndedIterator for Iter<'a, 'b, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.span.next_back()
+    }
+}
+
+pub struct Keys<'a, 'b, T, S = ()> {
+    inner: Iter<'a, 'b, T, S>,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Keys<'a, 'b, T, S> {
+    pub(crate) fn new(inner: Iter<'a, 'b, T, S>) -> Self {
+        Keys { inner }
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iterator for Keys<'a, 'b, T, S> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> DoubleEndedIterator for Keys<'a, 'b, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+pub struct Values<'a, 'b, T, S = ()> {
+    inner: Iter<'a, 'b, T, S>,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Values<'a, 'b, T, S> {
+    pub(crate) fn new(inner: Iter<'a, 'b, T, S>) -> Self {
+        Values { inner }
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iterator for Values<'a, 'b, T, S> {
+    type Item = &'b T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> DoubleEndedIterator for Values<'a, 'b, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+pub struct Range<'a, 'b, T, S = ()> {
+    span: Span<'a, 'b, T, S>,
+}
+
+impl<'a, 'b, T, S: Summary<T>> Range<'a, 'b, T, S> {
+    pub(crate) fn new(leaves: Leaves<'a, 'b, T, S>, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Self {
+        Range { span: Span::new(leaves, start, end) }
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iterator for Range<'a, 'b, T, S> {
+    type Item = (Vec<u8>, &'b T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.span.next_front()
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> DoubleEndedIterator for Range<'a, 'b, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.span.next_back()
+    }
+}
+
+/// Remaining children of a node, along with the length of the key leading to them.
+type ChildrenMut<'a, 'b, T> = (std::vec::IntoIter<(u8, &'b mut Child<'a, T, ()>)>, usize);
+
+pub struct IterMut<'a, 'b, T> {
+    root: Option<&'b mut Child<'a, T, ()>>,
+    stack: Vec<ChildrenMut<'a, 'b, T>>,
+    key: Vec<u8>,
+    term: u8,
+}
+
+impl<'a, 'b, T> IterMut<'a, 'b, T> {
+    pub(crate) fn new(root: Option<&'b mut Child<'a, T, ()>>, term: u8) -> Self {
+        IterMut { root, stack: Vec::new(), key: Vec::new(), term }
+    }
+
+    fn descend_first(&mut self, mut child: &'b mut Child<'a, T, ()>) -> (Vec<u8>, &'b mut T) {
+        loop {
+            match child {
+                Child::Leaf(suffix, value) => {
+                    self.key.extend_from_slice(suffix);
+                    return (self.key.clone(), value);
+                }
+                Child::Node(node) => {
+                    self.key.extend_from_slice(&node.prefix);
+                    let mut children = node.children_mut(self.term).into_iter();
+                    let (key, first) = children.next().unwrap();
+                    self.stack.push((children, self.key.len()));
+                    if key != self.term {
+                        self.key.push(key);
+                    }
+                    child = first;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T> Iterator for IterMut<'a, 'b, T> {
+    type Item = (Vec<u8>, &'b mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            return Some(self.descend_first(root));
+        }
+
+        loop {
+            let (children, depth) = self.stack.last_mut()?;
+            match children.next() {
+                Some((key, child)) => {
+                    self.key.truncate(*depth);
+                    if key != self.term {
+                        self.key.push(key);
+                    }
+                    return Some(self.descend_first(child));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+pub struct ValuesMut<'a, 'b, T> {
+    inner: IterMut<'a, 'b, T>,
+}
+
+impl<'a, 'b, T> ValuesMut<'a, 'b, T> {
+    pub(crate) fn new(inner: IterMut<'a, 'b, T>) -> Self {
+        ValuesMut { inner }
+    }
+}
+
+impl<'a, 'b, T> Iterator for ValuesMut<'a, 'b, T> {
+    type Item = &'b mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+}
+
+/// Remaining children of a node, along with the length of the key leading to them.
+type IntoChildren<'a, T, S> = (std::vec::IntoIter<(u8, Box<Child<'a, T, S>>)>, usize);
+
+pub struct IntoIter<'a, T, S = ()> {
+    root: Option<Child<'a, T, S>>,
+    stack: Vec<IntoChildren<'a, T, S>>,
+    key: Vec<u8>,
+    term: u8,
+}
+
+impl<'a, T, S: Summary<T>> IntoIter<'a, T, S> {
+    pub(crate) fn new(root: Option<Child<'a, T, S>>, prefix: Vec<u8>, term: u8) -> Self {
+        IntoIter { root, stack: Vec::new(), key: prefix, term }
+    }
+
+    fn descend_first(&mut self, mut child: Child<'a, T, S>) -> (Vec<u8>, T) {
+        loop {
+            match child {
+                Child::Leaf(suffix, value) => {
+                    self.key.extend_from_slice(&suffix);
+                    return (self.key.clone(), value);
+                }
+                Child::Node(node) => {
+                    self.key.extend_from_slice(&node.prefix);
+                    let mut children = node.into_children(self.term).into_iter();
+                    let (key, first) = children.next().unwrap();
+                    self.stack.push((children, self.key.len()));
+                    if key != self.term {
+                        self.key.push(key);
+                    }
+                    child = *first;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, S: Summary<T>> Iterator for IntoIter<'a, T, S> {
+    type Item = (Vec<u8>, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            return Some(self.descend_first(root));
+        }
+
+        loop {
+            let (children, depth) = self.stack.last_mut()?;
+            match children.next() {
+                Some((key, child)) => {
+                    self.key.truncate(*depth);
+                    if key != self.term {
+                        self.key.push(key);
+                    }
+                    return Some(self.descend_first(*child));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+pub struct PrefixesOf<'a, 'b, T, S = ()> {
+    child: Option<&'b Child<'a, T, S>>,
+    input: &'b [u8],
+    depth: usize,
+    term: u8,
+}
+
+impl<'a, 'b, T, S: Summary<T>> PrefixesOf<'a, 'b, T, S> {
+    pub(crate) fn new(root: Option<&'b Child<'a, T, S>>, input: &'b [u8], term: u8) -> Self {
+        PrefixesOf { child: root, input, depth: 0, term }
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> Iterator for PrefixesOf<'a, 'b, T, S> {
+    type Item = (usize, &'b T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let child = self.child.take()?;
+            let rest = &self.input[self.depth..];
+            match child {
+                Child::Leaf(suffix, value) => {
+                    return rest.starts_with(suffix).then_some((self.depth + suffix.len(), value));
+                }
+                Child::Node(node) => {
+                    if !rest.starts_with(&node.prefix) {
+                        return None;
+                    }
+
+                    let depth = self.depth + node.prefix.len();
+                    self.child = self.input.get(depth).and_then(|&byte| node.find_child(byte));
+                    self.depth = depth + 1;
+
+                    if let Some(Child::Leaf(_, value)) = node.find_child(self.term) {
+                        return Some((depth, value));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,22 @@
 #![deny(warnings)]
 
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{
+    Hash,
+    Hasher,
+};
+use std::iter::FromIterator;
 use std::mem;
+use std::ops::{
+    Bound,
+    RangeBounds,
+};
+use std::ptr;
 
 #[cfg(feature = "node4")]
 mod node4;
@@ -23,23 +39,125 @@ use self::node48::Node48 as DefaultNode;
 // always included
 mod node256;
 
+mod bitmap;
+
+mod cursor;
+
+mod entry;
+
+mod iter;
+
+mod subtrie;
+
+mod summary;
+
+pub use self::cursor::{
+    Cursor,
+    CursorMut,
+};
+
+pub use self::entry::{
+    Entry,
+    OccupiedEntry,
+    VacantEntry,
+};
+
+pub use self::iter::{
+    IntoIter,
+    Iter,
+    IterMut,
+    Keys,
+    PrefixesOf,
+    Range,
+    Values,
+    ValuesMut,
+};
+
+pub use self::subtrie::{
+    SubTrie,
+    SubTrieMut,
+};
+
+pub use self::summary::Summary;
+
+use self::iter::Leaves;
+
 #[cfg(all(not(feature = "node4"), not(feature = "node16"), not(feature = "node48")))]
 use self::node256::Node256 as DefaultNode;
 
-pub struct Trie<'a, T> {
-    root: Option<Child<'a, T>>,
+/// A trie mapping byte strings to values, which may also cache a [`Summary`] `S` of the entries
+/// below each node.
+///
+/// Values of summarized tries can only be changed through `insert`: `entry`, `get_mut`,
+/// `subtrie_mut`, `cursor_mut`, `iter_mut` and `values_mut` are only provided for tries without
+/// summaries, since changing a value in place would leave the summaries above it stale.
+#[derive(Clone)]
+pub struct Trie<'a, T, S = ()> {
+    root: Option<Child<'a, T, S>>,
+    len: usize,
     term: u8,
 }
 
 #[derive(Debug)]
 pub struct KeyContainsTerminator;
 
+/// Tries with different terminators can't share nodes.
+#[derive(Debug)]
+pub struct TerminatorMismatch;
+
+#[derive(Debug)]
+pub enum GraftError {
+    KeyContainsTerminator,
+    TerminatorMismatch,
+}
+
+impl From<KeyContainsTerminator> for GraftError {
+    fn from(_: KeyContainsTerminator) -> Self {
+        GraftError::KeyContainsTerminator
+    }
+}
+
+impl From<TerminatorMismatch> for GraftError {
+    fn from(_: TerminatorMismatch) -> Self {
+        GraftError::TerminatorMismatch
+    }
+}
+
+/// Entries already have a key starting with the prefix that others were to be moved to.
+#[derive(Debug)]
+pub struct PrefixOccupied;
+
+#[derive(Debug)]
+pub enum RenameError {
+    KeyContainsTerminator,
+    PrefixOccupied,
+}
+
+impl From<KeyContainsTerminator> for RenameError {
+    fn from(_: KeyContainsTerminator) -> Self {
+        RenameError::KeyContainsTerminator
+    }
+}
+
+impl From<PrefixOccupied> for RenameError {
+    fn from(_: PrefixOccupied) -> Self {
+        RenameError::PrefixOccupied
+    }
+}
+
+/// What to do with the entries already under a prefix when moving others to it.
+pub enum OnCollision<'f, T> {
+    /// Fail without moving anything.
+    Fail,
+    /// Remove them first.
+    Overwrite,
+    /// Keep them, updating the value of each key found under both prefixes with the moved one.
+    Merge(&'f mut dyn FnMut(&mut T, T)),
+}
+
 impl<'a, T> Trie<'a, T> {
     pub fn with_terminator(term: u8) -> Trie<'a, T> {
-        Trie {
-            root: None,
-            term: term,
-        }
+        Self::summarized(term)
     }
 
     pub fn for_ascii() -> Trie<'a, T> {
@@ -49,6 +167,18 @@ impl<'a, T> Trie<'a, T> {
     pub fn for_utf8() -> Trie<'a, T> {
         Self::with_terminator(0xff)
     }
+}
+
+impl<'a, T, S: Summary<T>> Trie<'a, T, S> {
+    /// Create a trie with the given terminator, caching the summary of the entries below each
+    /// inner node.
+    pub fn summarized(term: u8) -> Trie<'a, T, S> {
+        Trie {
+            root: None,
+            len: 0,
+            term,
+        }
+    }
 
     pub fn insert(&mut self, key: &[u8], value: T) -> Result<Option<T>, KeyContainsTerminator> {
         if !key.contains(&self.term) {
@@ -58,21 +188,25 @@ impl<'a, T> Trie<'a, T> {
         }
     }
 
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
     pub unsafe fn insert_unchecked(&mut self, key: &[u8], value: T) -> Option<T> {
         self.insert_impl(key, value)
     }
 
     fn insert_impl(&mut self, key: &[u8], value: T) -> Option<T> {
-        match self.root {
+        let replaced = match self.root {
             None => {
-                let mut node = Node::new();
-                let inserted = node.insert(key, value, self.term);
-                self.root = Some(Child::Node(node));
-                inserted
+                self.root = Some(Child::Leaf(key.to_vec(), value));
+                None
             }
-            Some(Child::Node(ref mut node)) => node.insert(key, value, self.term),
-            Some(Child::Leaf(_))            => unreachable!(),
+            Some(ref mut root) => root.insert(key, value, self.term),
+        };
+        if replaced.is_none() {
+            self.len += 1;
         }
+        replaced
     }
 
     pub fn contains(&self, key: &[u8]) -> Result<bool, KeyContainsTerminator> {
@@ -83,16 +217,15 @@ impl<'a, T> Trie<'a, T> {
         }
     }
 
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
     pub unsafe fn contains_unchecked(&self, key: &[u8]) -> bool {
         self.contains_impl(key)
     }
 
     fn contains_impl(&self, key: &[u8]) -> bool {
-        match self.root {
-            None                        => false,
-            Some(Child::Node(ref node)) => node.contains(key, self.term),
-            Some(Child::Leaf(_))        => unreachable!(),
-        }
+        self.get_impl(key).is_some()
     }
 
     pub fn get(&self, key: &[u8]) -> Result<Option<&T>, KeyContainsTerminator> {
@@ -103,252 +236,3421 @@ impl<'a, T> Trie<'a, T> {
         }
     }
 
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
     pub unsafe fn get_unchecked(&self, key: &[u8]) -> Option<&T> {
         self.get_impl(key)
     }
 
     fn get_impl(&self, key: &[u8]) -> Option<&T> {
-        match self.root {
-            None                        => None,
-            Some(Child::Node(ref node)) => node.get(key, self.term),
-            Some(Child::Leaf(_))        => unreachable!(),
+        self.root.as_ref().and_then(|root| root.get(key, self.term))
+    }
+
+    pub fn longest_prefix(&self, key: &[u8]) -> Result<Option<(usize, &T)>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.longest_prefix_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.root.is_none()
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn longest_prefix_unchecked(&self, key: &[u8]) -> Option<(usize, &T)> {
+        self.longest_prefix_impl(key)
     }
-}
 
-struct Node<'a, T: 'a>(Box<dyn NodeImpl<'a, T> + 'a>);
+    fn longest_prefix_impl(&self, key: &[u8]) -> Option<(usize, &T)> {
+        self.root.as_ref().and_then(|root| root.longest_prefix(key, self.term))
+    }
 
-trait NodeImpl<'a, T> {
-    fn insert_child(&mut self, key: u8, child: Child<'a, T>) -> Result<Option<Child<'a, T>>, Child<'a, T>>;
+    pub fn prefixes_of<'b>(&'b self, input: &'b [u8]) -> Result<PrefixesOf<'a, 'b, T, S>, KeyContainsTerminator> {
+        if !input.contains(&self.term) {
+            Ok(self.prefixes_of_impl(input))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
 
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) -> Result<(), Child<'a, T>>;
+    /// # Safety
+    ///
+    /// The input must not contain the trie's terminator.
+    pub unsafe fn prefixes_of_unchecked<'b>(&'b self, input: &'b [u8]) -> PrefixesOf<'a, 'b, T, S> {
+        self.prefixes_of_impl(input)
+    }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>>;
+    fn prefixes_of_impl<'b>(&'b self, input: &'b [u8]) -> PrefixesOf<'a, 'b, T, S> {
+        PrefixesOf::new(self.root.as_ref(), input, self.term)
+    }
 
-    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T> + 'a>;
-}
+    pub fn remove(&mut self, key: &[u8]) -> Result<Option<T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.remove_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
 
-impl<'a, T> Node<'a, T> {
-    fn new() -> Self {
-        Node(Box::new(DefaultNode::default()))
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn remove_unchecked(&mut self, key: &[u8]) -> Option<T> {
+        self.remove_impl(key)
     }
 
-    fn insert(&mut self, key: &[u8], value: T, term: u8) -> Option<T> {
-        if key.is_empty() {
-            self.insert_child(term, Child::Leaf(value))
-                .map(|n| n.to_leaf().unwrap())
+    /// Insert every entry of `iter`, stopping at the first key containing the terminator.
+    pub fn try_extend<K, I>(&mut self, iter: I) -> Result<(), KeyContainsTerminator>
+        where K: AsRef<[u8]>,
+              I: IntoIterator<Item = (K, T)>,
+    {
+        for (key, value) in iter {
+            self.insert(key.as_ref(), value)?;
+        }
+        Ok(())
+    }
+
+    fn remove_impl(&mut self, key: &[u8]) -> Option<T> {
+        let removed = match self.root {
+            None => None,
+            Some(Child::Leaf(ref suffix, _)) => {
+                if suffix[..] != *key {
+                    return None;
+                }
+                self.root.take().and_then(Child::into_leaf)
+            }
+            Some(Child::Node(ref mut node)) => {
+                let removed = node.remove(key, self.term);
+                if removed.is_some() {
+                    self.root.as_mut().unwrap().compress(self.term);
+                }
+                removed
+            }
+        };
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Keep only the entries for which `f` returns true, visiting them in key order.
+    pub fn retain<F: FnMut(&[u8], &mut T) -> bool>(&mut self, mut f: F) {
+        let root = match self.root {
+            Some(ref mut root) => root,
+            None               => return,
+        };
+
+        let mut removed = 0;
+        if !root.retain(&mut Vec::new(), &mut f, self.term, &mut removed) {
+            self.root = None;
+        }
+        self.len -= removed;
+    }
+
+    /// Remove all entries whose key starts with the given prefix, returning how many there were.
+    pub fn remove_prefix(&mut self, prefix: &[u8]) -> Result<usize, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.remove_prefix_impl(prefix))
         } else {
-            self.update_child(key[0], Child::Node(Node::new()));
-            let child = self.find_child_mut(key[0]).unwrap().as_node_mut().unwrap();
-            child.insert(&key[1..], value, term)
+            Err(KeyContainsTerminator)
         }
     }
 
-    fn contains(&self, key: &[u8], term: u8) -> bool {
-        self.get(key, term).is_some()
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn remove_prefix_unchecked(&mut self, prefix: &[u8]) -> usize {
+        self.remove_prefix_impl(prefix)
     }
 
-    fn get(&self, key: &[u8], term: u8) -> Option<&T> {
-        if key.is_empty() {
-            self.find_child(term)
-                .map(|n| n.as_leaf().unwrap())
+    fn remove_prefix_impl(&mut self, prefix: &[u8]) -> usize {
+        let count = self.detach_prefix(prefix).map_or(0, |(_, subtree)| subtree.count());
+        self.len -= count;
+        count
+    }
+
+    /// Remove all entries whose key starts with the given prefix, yielding them in key order.
+    pub fn drain_prefix(&mut self, prefix: &[u8]) -> Result<IntoIter<'a, T, S>, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.drain_prefix_impl(prefix))
         } else {
-            self.find_child(key[0])
-                .and_then(|n| n.as_node())
-                .and_then(|node| node.get(&key[1..], term))
+            Err(KeyContainsTerminator)
         }
     }
 
-    fn insert_child(&mut self, key: u8, child: Child<'a, T>) -> Option<Child<'a, T>> {
-        let result = self.0.insert_child(key, child);
-        match result {
-            Ok(replaced_child) => replaced_child,
-            Err(child)         => {
-                self.upgrade();
-                self.insert_child(key, child)
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn drain_prefix_unchecked(&mut self, prefix: &[u8]) -> IntoIter<'a, T, S> {
+        self.drain_prefix_impl(prefix)
+    }
+
+    fn drain_prefix_impl(&mut self, prefix: &[u8]) -> IntoIter<'a, T, S> {
+        match self.detach_prefix(prefix) {
+            Some((key, subtree)) => {
+                self.len -= subtree.count();
+                IntoIter::new(Some(subtree), key, self.term)
             }
+            None                 => IntoIter::new(None, Vec::new(), self.term),
         }
     }
 
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) {
-        let result = self.0.update_child(key, child);
-        if let Err(child) = result {
-            self.upgrade();
-            self.update_child(key, child)
+    /// Move all entries whose key starts with `old` to start with `new` instead, returning whether
+    /// there were any.
+    ///
+    /// Only the nodes along both prefixes are visited, apart from dropping the entries removed by
+    /// `OnCollision::Overwrite`.
+    pub fn rename_prefix(&mut self, old: &[u8], new: &[u8], on_collision: OnCollision<'_, T>) -> Result<bool, RenameError> {
+        if !old.contains(&self.term) && !new.contains(&self.term) {
+            Ok(self.rename_prefix_impl(old, new, on_collision)?)
+        } else {
+            Err(RenameError::KeyContainsTerminator)
         }
     }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>> {
-        self.0.find_child(key)
+    /// # Safety
+    ///
+    /// The prefixes must not contain the trie's terminator.
+    pub unsafe fn rename_prefix_unchecked(&mut self, old: &[u8], new: &[u8], on_collision: OnCollision<'_, T>) -> Result<bool, PrefixOccupied> {
+        self.rename_prefix_impl(old, new, on_collision)
     }
 
-    fn upgrade(&mut self) {
-        take_mut::take(&mut self.0, NodeImpl::upgrade);
+    fn rename_prefix_impl(&mut self, old: &[u8], new: &[u8], on_collision: OnCollision<'_, T>) -> Result<bool, PrefixOccupied> {
+        let root = match self.root {
+            Some(ref root) => root,
+            None           => return Ok(false),
+        };
+        let moved = match root.find_prefix(old) {
+            Some((moved, _)) => moved,
+            None             => return Ok(false),
+        };
+
+        // Entries under `new` which are also under `old` move along, so they don't collide
+        let occupied = !new.starts_with(old) && root.find_prefix(new)
+            .is_some_and(|(subtree, _)| !ptr::eq(subtree, moved));
+        let clear = matches!(on_collision, OnCollision::Overwrite);
+        let mut overwrite = |existing: &mut T, value| *existing = value;
+        let resolve = match on_collision {
+            OnCollision::Fail if occupied              => return Err(PrefixOccupied),
+            OnCollision::Fail | OnCollision::Overwrite => &mut overwrite,
+            OnCollision::Merge(resolve)                => resolve,
+        };
+
+        let (key, mut subtree) = self.detach_prefix(old).unwrap();
+        if clear {
+            if let Some((_, cleared)) = self.detach_prefix(new) {
+                self.len -= cleared.count();
+            }
+        }
+
+        // Key the subtree from the root, so that it can be merged back into it
+        subtree.rekey(old.len() - key.len(), new);
+        self.len -= self.merge_subtree(subtree, resolve);
+        Ok(true)
     }
 
-    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'_, T>> {
-        unsafe { mem::transmute(self.find_child(key)) }
+    /// Move all entries of `other` into this trie, prepending the given prefix to their keys and
+    /// replacing the values of existing keys.
+    pub fn graft(&mut self, prefix: &[u8], other: Self) -> Result<(), GraftError> {
+        if !prefix.contains(&self.term) {
+            Ok(self.graft_impl(prefix, other)?)
+        } else {
+            Err(GraftError::KeyContainsTerminator)
+        }
     }
-}
 
-enum Child<'a, T: 'a> {
-    Node(Node<'a, T>),
-    Leaf(T),
-}
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn graft_unchecked(&mut self, prefix: &[u8], other: Self) -> Result<(), TerminatorMismatch> {
+        self.graft_impl(prefix, other)
+    }
 
-impl<'a, T> Child<'a, T> {
-    fn as_node(&self) -> Option<&Node<'a, T>> {
-        if let Child::Node(ref node) = self {
-            Some(node)
+    fn graft_impl(&mut self, prefix: &[u8], other: Self) -> Result<(), TerminatorMismatch> {
+        if other.term != self.term {
+            return Err(TerminatorMismatch);
+        }
+        if let Some(mut subtree) = other.root {
+            subtree.rekey(0, prefix);
+            self.len += other.len - self.merge_subtree(subtree, &mut |existing, value| *existing = value);
+        }
+        Ok(())
+    }
+
+    /// Move all entries whose key starts with the given prefix into a new trie, removing the prefix
+    /// from their keys, without visiting them.
+    #[cfg(feature = "counts")]
+    pub fn prune(&mut self, prefix: &[u8]) -> Result<Self, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.prune_impl(prefix))
         } else {
-            None
+            Err(KeyContainsTerminator)
         }
     }
 
-    fn as_node_mut(&mut self) -> Option<&mut Node<'a, T>> {
-        if let Child::Node(ref mut node) = self {
-            Some(node)
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    #[cfg(feature = "counts")]
+    pub unsafe fn prune_unchecked(&mut self, prefix: &[u8]) -> Self {
+        self.prune_impl(prefix)
+    }
+
+    #[cfg(feature = "counts")]
+    fn prune_impl(&mut self, prefix: &[u8]) -> Self {
+        let mut pruned = Self::summarized(self.term);
+        if let Some((key, mut subtree)) = self.detach_prefix(prefix) {
+            pruned.len = subtree.count();
+            self.len -= pruned.len;
+            subtree.rekey(prefix.len() - key.len(), &[]);
+            pruned.root = Some(subtree);
+        }
+        pruned
+    }
+
+    /// Merge a subtree keyed from the root into the trie, returning how many of its keys were
+    /// already there, leaving the trie's length to the caller.
+    fn merge_subtree(&mut self, subtree: Child<'a, T, S>, resolve: &mut dyn FnMut(&mut T, T)) -> usize {
+        match self.root {
+            None               => {
+                self.root = Some(subtree);
+                0
+            }
+            Some(ref mut root) => root.merge(subtree, self.term, resolve),
+        }
+    }
+
+    /// Detach the subtree holding all keys starting with the given prefix, along with the key
+    /// leading to it, leaving the trie's length to the caller.
+    fn detach_prefix(&mut self, prefix: &[u8]) -> Option<(Vec<u8>, Child<'a, T, S>)> {
+        let root = self.root.as_mut()?;
+        let (subtree, depth) = if root.holds_prefix(prefix) {
+            (self.root.take().unwrap(), 0)
         } else {
-            None
+            let detached = root.as_node_mut()?.remove_prefix(prefix, self.term)?;
+            root.compress(self.term);
+            detached
+        };
+
+        Some((prefix[..depth].to_vec(), subtree))
+    }
+
+    /// Move all entries with a key greater than or equal to the given one into a new trie.
+    pub fn split_off(&mut self, key: &[u8]) -> Result<Self, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.split_off_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
         }
     }
 
-    fn as_leaf(&self) -> Option<&T> {
-        if let Child::Leaf(ref value) = self {
-            Some(value)
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn split_off_unchecked(&mut self, key: &[u8]) -> Self {
+        self.split_off_impl(key)
+    }
+
+    fn split_off_impl(&mut self, key: &[u8]) -> Self {
+        let mut other = Self::summarized(self.term);
+        if let Some(root) = self.root.take() {
+            let ((below, above), moved) = root.split(key, self.term);
+            self.root = below;
+            other.root = above;
+            other.len = moved;
+            self.len -= moved;
+        }
+        other
+    }
+
+    /// Move all entries of `other` into this trie, replacing the values of existing keys.
+    ///
+    /// Fails without moving anything if the tries have different terminators.
+    pub fn append(&mut self, other: &mut Self) -> Result<(), TerminatorMismatch> {
+        if other.term != self.term {
+            return Err(TerminatorMismatch);
+        }
+
+        let root = match other.root.take() {
+            Some(root) => root,
+            None       => return Ok(()),
+        };
+        let len = mem::replace(&mut other.len, 0);
+        match self.root {
+            None               => self.root = Some(root),
+            Some(ref mut mine) => self.len -= mine.merge(root, self.term, &mut |existing, value| *existing = value),
+        }
+        self.len += len;
+        Ok(())
+    }
+
+    pub fn first_key_value(&self) -> Option<(Vec<u8>, &T)> {
+        self.edge(false)
+    }
+
+    pub fn last_key_value(&self) -> Option<(Vec<u8>, &T)> {
+        self.edge(true)
+    }
+
+    fn edge(&self, last: bool) -> Option<(Vec<u8>, &T)> {
+        let mut key = Vec::new();
+        let value = self.root.as_ref()?.edge(last, &mut key, self.term);
+        Some((key, value))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(Vec<u8>, T)> {
+        self.pop(false)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(Vec<u8>, T)> {
+        self.pop(true)
+    }
+
+    fn pop(&mut self, last: bool) -> Option<(Vec<u8>, T)> {
+        let popped = match self.root.take()? {
+            Child::Leaf(key, value) => (key, value),
+            Child::Node(mut node)   => {
+                let mut key = Vec::new();
+                let value = node.pop(last, &mut key, self.term);
+                let mut root = Child::Node(node);
+                root.compress(self.term);
+                self.root = Some(root);
+                (key, value)
+            }
+        };
+        self.len -= 1;
+        Some(popped)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Iter<'a, '_, T, S> {
+        Iter::new(self.leaves())
+    }
+
+    pub fn keys(&self) -> Keys<'a, '_, T, S> {
+        Keys::new(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'a, '_, T, S> {
+        Values::new(self.iter())
+    }
+
+    pub fn prefix_iter(&self, prefix: &[u8]) -> Result<Iter<'a, '_, T, S>, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.prefix_iter_impl(prefix))
         } else {
-            None
+            Err(KeyContainsTerminator)
         }
     }
 
-    fn to_leaf(self) -> Option<T> {
-        if let Child::Leaf(value) = self {
-            Some(value)
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn prefix_iter_unchecked(&self, prefix: &[u8]) -> Iter<'a, '_, T, S> {
+        self.prefix_iter_impl(prefix)
+    }
+
+    fn prefix_iter_impl(&self, prefix: &[u8]) -> Iter<'a, '_, T, S> {
+        let (subtree, depth) = self.root.as_ref()
+            .and_then(|root| root.find_prefix(prefix))
+            .unzip();
+        let depth = depth.unwrap_or(0);
+        Iter::new(Leaves::new(subtree, prefix[..depth].to_vec(), self.term))
+    }
+
+    /// Borrow the entries whose key starts with the given prefix as a trie of their own, with keys
+    /// relative to the prefix, or `None` if there are none.
+    pub fn subtrie(&self, prefix: &[u8]) -> Result<Option<SubTrie<'a, '_, T, S>>, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.subtrie_impl(prefix))
         } else {
-            None
+            Err(KeyContainsTerminator)
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fmt::Debug;
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn subtrie_unchecked(&self, prefix: &[u8]) -> Option<SubTrie<'a, '_, T, S>> {
+        self.subtrie_impl(prefix)
+    }
 
-    #[test]
-    fn test_readme_insert_lookup_example() {
-        let mut map = Trie::for_utf8();
-        map.insert(b"a", 0).unwrap();
-        map.insert(b"ac", 1).unwrap();
+    fn subtrie_impl(&self, prefix: &[u8]) -> Option<SubTrie<'a, '_, T, S>> {
+        let (subtree, depth) = self.root.as_ref()?.find_prefix(prefix)?;
+        Some(SubTrie::new(subtree, prefix.len() - depth, self.term))
+    }
 
-        assert_eq!(map.get(b"a").unwrap(), Some(&0));
-        assert_eq!(map.get(b"ac").unwrap(), Some(&1));
-        assert_eq!(map.get(b"ab").unwrap(), None);
+    /// Count the entries whose key starts with the given prefix, without visiting them.
+    #[cfg(feature = "counts")]
+    pub fn count_prefix(&self, prefix: &[u8]) -> Result<usize, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.count_prefix_impl(prefix))
+        } else {
+            Err(KeyContainsTerminator)
+        }
     }
 
-    trait TrieTestExtensions<T: Clone + PartialEq + Debug> {
-        fn check_insertion(&mut self, key: &[u8], value: T);
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    #[cfg(feature = "counts")]
+    pub unsafe fn count_prefix_unchecked(&self, prefix: &[u8]) -> usize {
+        self.count_prefix_impl(prefix)
+    }
 
-        fn check_existence(&mut self, key: &[u8], value: T);
+    #[cfg(feature = "counts")]
+    fn count_prefix_impl(&self, prefix: &[u8]) -> usize {
+        self.root.as_ref()
+            .and_then(|root| root.find_prefix(prefix))
+            .map_or(0, |(subtree, _)| subtree.count())
     }
 
-    impl<'a, T: 'a + Clone + PartialEq + Debug> TrieTestExtensions<T> for Trie<'a, T> {
-        fn check_insertion(&mut self, key: &[u8], value: T) {
-            self.insert(key, value.clone()).unwrap();
-            self.check_existence(key, value);
+    /// Count the entries with a key less than the given one.
+    #[cfg(feature = "counts")]
+    pub fn rank(&self, key: &[u8]) -> Result<usize, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.rank_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
         }
+    }
 
-        fn check_existence(&mut self, key: &[u8], value: T) {
-            assert_eq!(self.get(key).unwrap(), Some(&value));
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    #[cfg(feature = "counts")]
+    pub unsafe fn rank_unchecked(&self, key: &[u8]) -> usize {
+        self.rank_impl(key)
+    }
+
+    #[cfg(feature = "counts")]
+    fn rank_impl(&self, key: &[u8]) -> usize {
+        self.root.as_ref().map_or(0, |root| root.rank(key, self.term))
+    }
+
+    /// Find the n-th entry in key order, starting from 0.
+    #[cfg(feature = "counts")]
+    pub fn select(&self, n: usize) -> Option<(Vec<u8>, &T)> {
+        if n >= self.len {
+            return None;
         }
+        let mut key = Vec::new();
+        let value = self.root.as_ref()?.select(n, &mut key, self.term);
+        Some((key, value))
     }
 
-    #[test]
-    fn it_works() {
-        let mut trie = Trie::for_utf8();
-        trie.check_insertion(b"the answer", 42);
+    /// Pick an entry uniformly at random, given a function returning a uniformly random number
+    /// below the one it's called with.
+    #[cfg(feature = "counts")]
+    pub fn sample<R: FnMut(usize) -> usize>(&self, mut random_below: R) -> Option<(Vec<u8>, &T)> {
+        if self.is_empty() {
+            return None;
+        }
+        self.select(random_below(self.len))
     }
 
-    #[test]
-    fn it_works_for_empty_strings() {
-        let mut trie = Trie::for_utf8();
-        trie.check_insertion(b"", 1);
+    /// Combine the summaries of the entries whose key starts with the given prefix, in key order.
+    pub fn aggregate_prefix(&self, prefix: &[u8]) -> Result<S, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.aggregate_prefix_impl(prefix))
+        } else {
+            Err(KeyContainsTerminator)
+        }
     }
 
-    #[test]
-    fn it_is_empty_by_default() {
-        let trie = Trie::<()>::for_utf8();
-        assert!(trie.is_empty());
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn aggregate_prefix_unchecked(&self, prefix: &[u8]) -> S {
+        self.aggregate_prefix_impl(prefix)
     }
 
-    #[test]
-    fn it_doesnt_overwrite_entries_with_a_common_prefix() {
-        let mut trie = Trie::for_utf8();
-        trie.insert(b"a", 1).unwrap();
-        trie.insert(b"ab", 2).unwrap();
-        assert_eq!(trie.get(b"a").unwrap(), Some(&1));
-        assert_eq!(trie.get(b"ab").unwrap(), Some(&2));
+    fn aggregate_prefix_impl(&self, prefix: &[u8]) -> S {
+        self.root.as_ref()
+            .and_then(|root| root.find_prefix(prefix))
+            .map_or_else(S::default, |(subtree, _)| subtree.summarize(S::default()))
     }
 
-    #[test]
-    fn it_can_store_more_than_4_parallel_entries() {
-        let mut trie = Trie::for_utf8();
-        // 1) insert
-        trie.check_insertion(b"a", 1);
-        trie.check_insertion(b"b", 2);
-        trie.check_insertion(b"c", 3);
-        trie.check_insertion(b"d", 4);
-        trie.check_insertion(b"e", 5);
-        // 2) verify
-        trie.check_existence(b"a", 1);
-        trie.check_existence(b"b", 2);
-        trie.check_existence(b"c", 3);
-        trie.check_existence(b"d", 4);
-        trie.check_existence(b"e", 5);
+    /// Combine the summaries of the entries whose key is in the given range, in key order.
+    pub fn aggregate_range<K, R>(&self, range: R) -> Result<S, KeyContainsTerminator>
+        where K: AsRef<[u8]> + ?Sized,
+              R: RangeBounds<K>
+    {
+        let start = range.start_bound().map(|key| key.as_ref());
+        let end = range.end_bound().map(|key| key.as_ref());
+        if !bound_contains(start, self.term) && !bound_contains(end, self.term) {
+            Ok(self.aggregate_range_impl(start, end))
+        } else {
+            Err(KeyContainsTerminator)
+        }
     }
 
-    #[test]
-    fn it_can_store_more_than_16_parallel_entries() {
+    /// # Safety
+    ///
+    /// The range's bounds must not contain the trie's terminator.
+    pub unsafe fn aggregate_range_unchecked<K, R>(&self, range: R) -> S
+        where K: AsRef<[u8]> + ?Sized,
+              R: RangeBounds<K>
+    {
+        let start = range.start_bound().map(|key| key.as_ref());
+        let end = range.end_bound().map(|key| key.as_ref());
+        self.aggregate_range_impl(start, end)
+    }
+
+    fn aggregate_range_impl(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> S {
+        self.root.as_ref().map_or_else(S::default, |root| root.aggregate(start, end, S::default(), self.term))
+    }
+
+    pub fn range<K, R>(&self, range: R) -> Result<Range<'a, '_, T, S>, KeyContainsTerminator>
+        where K: AsRef<[u8]> + ?Sized,
+              R: RangeBounds<K>
+    {
+        let start = range.start_bound().map(|key| key.as_ref());
+        let end = range.end_bound().map(|key| key.as_ref());
+        if !bound_contains(start, self.term) && !bound_contains(end, self.term) {
+            Ok(self.range_impl(start, end))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The range's bounds must not contain the trie's terminator.
+    pub unsafe fn range_unchecked<K, R>(&self, range: R) -> Range<'a, '_, T, S>
+        where K: AsRef<[u8]> + ?Sized,
+              R: RangeBounds<K>
+    {
+        let start = range.start_bound().map(|key| key.as_ref());
+        let end = range.end_bound().map(|key| key.as_ref());
+        self.range_impl(start, end)
+    }
+
+    fn range_impl(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Range<'a, '_, T, S> {
+        Range::new(self.leaves(), start.map(<[u8]>::to_vec), end.map(<[u8]>::to_vec))
+    }
+
+    /// Find the entry with the largest key less than or equal to the given one.
+    pub fn floor(&self, key: &[u8]) -> Result<Option<(Vec<u8>, &T)>, KeyContainsTerminator> {
+        self.nearest(Bound::Included(key), false)
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn floor_unchecked(&self, key: &[u8]) -> Option<(Vec<u8>, &T)> {
+        self.nearest_impl(Bound::Included(key), false)
+    }
+
+    /// Find the entry with the smallest key greater than or equal to the given one.
+    pub fn ceiling(&self, key: &[u8]) -> Result<Option<(Vec<u8>, &T)>, KeyContainsTerminator> {
+        self.nearest(Bound::Included(key), true)
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn ceiling_unchecked(&self, key: &[u8]) -> Option<(Vec<u8>, &T)> {
+        self.nearest_impl(Bound::Included(key), true)
+    }
+
+    /// Find the entry with the largest key strictly less than the given one.
+    pub fn lower(&self, key: &[u8]) -> Result<Option<(Vec<u8>, &T)>, KeyContainsTerminator> {
+        self.nearest(Bound::Excluded(key), false)
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn lower_unchecked(&self, key: &[u8]) -> Option<(Vec<u8>, &T)> {
+        self.nearest_impl(Bound::Excluded(key), false)
+    }
+
+    /// Find the entry with the smallest key strictly greater than the given one.
+    pub fn higher(&self, key: &[u8]) -> Result<Option<(Vec<u8>, &T)>, KeyContainsTerminator> {
+        self.nearest(Bound::Excluded(key), true)
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn higher_unchecked(&self, key: &[u8]) -> Option<(Vec<u8>, &T)> {
+        self.nearest_impl(Bound::Excluded(key), true)
+    }
+
+    fn nearest(&self, bound: Bound<&[u8]>, forward: bool) -> Result<Option<(Vec<u8>, &T)>, KeyContainsTerminator> {
+        if !bound_contains(bound, self.term) {
+            Ok(self.nearest_impl(bound, forward))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    fn nearest_impl(&self, bound: Bound<&[u8]>, forward: bool) -> Option<(Vec<u8>, &T)> {
+        let mut leaves = self.leaves();
+        let value = leaves.seek(bound, forward)?;
+        Some((leaves.key().to_vec(), value))
+    }
+
+    pub fn cursor(&self) -> Cursor<'a, '_, T, S> {
+        Cursor::new(self.leaves(), self.term)
+    }
+
+    fn leaves(&self) -> Leaves<'a, '_, T, S> {
+        Leaves::new(self.root.as_ref(), Vec::new(), self.term)
+    }
+}
+
+// Handing out mutable references to values would let them change under the subtrees' summaries,
+// so only tries without any get them (see `Trie`).
+impl<'a, T> Trie<'a, T> {
+    pub fn entry(&mut self, key: &[u8]) -> Result<Entry<'a, '_, T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.entry_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn entry_unchecked(&mut self, key: &[u8]) -> Entry<'a, '_, T> {
+        self.entry_impl(key)
+    }
+
+    fn entry_impl(&mut self, key: &[u8]) -> Entry<'a, '_, T> {
+        Entry::new(&mut self.root, &mut self.len, key, self.term)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Result<Option<&mut T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.get_mut_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn get_mut_unchecked(&mut self, key: &[u8]) -> Option<&mut T> {
+        self.get_mut_impl(key)
+    }
+
+    fn get_mut_impl(&mut self, key: &[u8]) -> Option<&mut T> {
+        let term = self.term;
+        self.root.as_mut().and_then(|root| root.get_mut(key, term))
+    }
+
+    /// Borrow the entries whose key starts with the given prefix mutably, see `Trie::subtrie`.
+    pub fn subtrie_mut(&mut self, prefix: &[u8]) -> Result<Option<SubTrieMut<'a, '_, T>>, KeyContainsTerminator> {
+        if !prefix.contains(&self.term) {
+            Ok(self.subtrie_mut_impl(prefix))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The prefix must not contain the trie's terminator.
+    pub unsafe fn subtrie_mut_unchecked(&mut self, prefix: &[u8]) -> Option<SubTrieMut<'a, '_, T>> {
+        self.subtrie_mut_impl(prefix)
+    }
+
+    fn subtrie_mut_impl(&mut self, prefix: &[u8]) -> Option<SubTrieMut<'a, '_, T>> {
+        SubTrieMut::new(&mut self.root, &mut self.len, prefix, self.term)
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'a, '_, T> {
+        CursorMut::new(&mut self.root, self.term)
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'a, '_, T> {
+        IterMut::new(self.root.as_mut(), self.term)
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'a, '_, T> {
+        ValuesMut::new(self.iter_mut())
+    }
+}
+
+impl<'a, 'b, T, S: Summary<T>> IntoIterator for &'b Trie<'a, T, S> {
+    type Item = (Vec<u8>, &'b T);
+    type IntoIter = Iter<'a, 'b, T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 'b, T> IntoIterator for &'b mut Trie<'a, T> {
+    type Item = (Vec<u8>, &'b mut T);
+    type IntoIter = IterMut<'a, 'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<'a, T, S: Summary<T>> IntoIterator for Trie<'a, T, S> {
+    type Item = (Vec<u8>, T);
+    type IntoIter = IntoIter<'a, T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self.root, Vec::new(), self.term)
+    }
+}
+
+impl<'a, T: fmt::Debug, S: Summary<T>> fmt::Debug for Trie<'a, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Equality and hashing only depend on the entries, not on the terminator or the node layout.
+
+impl<'a, T: PartialEq, S: Summary<T>> PartialEq for Trie<'a, T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.term == other.term && self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<'a, T: Eq, S: Summary<T>> Eq for Trie<'a, T, S> {}
+
+impl<'a, T: Hash, S: Summary<T>> Hash for Trie<'a, T, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.term);
+        state.write_usize(self.len);
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<'a, T, S: Summary<T>> Default for Trie<'a, T, S> {
+    fn default() -> Self {
+        Self::summarized(0xff)
+    }
+}
+
+/// # Panics
+///
+/// Panics if a key contains the terminator, see `Trie::try_extend` for a fallible version.
+impl<'a, K: AsRef<[u8]>, T, S: Summary<T>> Extend<(K, T)> for Trie<'a, T, S> {
+    fn extend<I: IntoIterator<Item = (K, T)>>(&mut self, iter: I) {
+        self.try_extend(iter).expect("key contains the terminator");
+    }
+}
+
+/// Collects into a `Trie::for_utf8`.
+///
+/// # Panics
+///
+/// Panics if a key contains the terminator, see `Trie::try_extend` for a fallible version.
+impl<'a, K: AsRef<[u8]>, T, S: Summary<T>> FromIterator<(K, T)> for Trie<'a, T, S> {
+    fn from_iter<I: IntoIterator<Item = (K, T)>>(iter: I) -> Self {
+        let mut trie = Self::default();
+        trie.extend(iter);
+        trie
+    }
+}
+
+// UTF-8 strings never contain 0xff, so they always fit in a `Trie::for_utf8`.
+
+impl<'a, T> From<BTreeMap<String, T>> for Trie<'a, T> {
+    fn from(map: BTreeMap<String, T>) -> Self {
+        let mut trie = Self::for_utf8();
+        for (key, value) in map {
+            unsafe { trie.insert_unchecked(key.as_bytes(), value); }
+        }
+        trie
+    }
+}
+
+impl<'a, T> From<HashMap<String, T>> for Trie<'a, T> {
+    fn from(map: HashMap<String, T>) -> Self {
+        let mut trie = Self::for_utf8();
+        for (key, value) in map {
+            unsafe { trie.insert_unchecked(key.as_bytes(), value); }
+        }
+        trie
+    }
+}
+
+impl<'a, T> TryFrom<BTreeMap<Vec<u8>, T>> for Trie<'a, T> {
+    type Error = KeyContainsTerminator;
+
+    fn try_from(map: BTreeMap<Vec<u8>, T>) -> Result<Self, Self::Error> {
+        let mut trie = Self::for_utf8();
+        trie.try_extend(map)?;
+        Ok(trie)
+    }
+}
+
+impl<'a, T> TryFrom<HashMap<Vec<u8>, T>> for Trie<'a, T> {
+    type Error = KeyContainsTerminator;
+
+    fn try_from(map: HashMap<Vec<u8>, T>) -> Result<Self, Self::Error> {
+        let mut trie = Self::for_utf8();
+        trie.try_extend(map)?;
+        Ok(trie)
+    }
+}
+
+impl<'a, T, S: Summary<T>> From<Trie<'a, T, S>> for BTreeMap<Vec<u8>, T> {
+    fn from(trie: Trie<'a, T, S>) -> Self {
+        trie.into_iter().collect()
+    }
+}
+
+impl<'a, T, S: Summary<T>> From<Trie<'a, T, S>> for HashMap<Vec<u8>, T> {
+    fn from(trie: Trie<'a, T, S>) -> Self {
+        trie.into_iter().collect()
+    }
+}
+
+struct Node<'a, T: 'a, S: 'a> {
+    prefix: Vec<u8>,
+    inner: Box<dyn NodeImpl<'a, T, S> + 'a>,
+    /// Number of entries below the node.
+    #[cfg(feature = "counts")]
+    count: usize,
+    /// Summary of the entries below the node.
+    summary: S,
+}
+
+trait NodeImpl<'a, T, S> {
+    fn insert_child(&mut self, key: u8, child: Child<'a, T, S>) -> Result<Option<Child<'a, T, S>>, Child<'a, T, S>>;
+
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>>;
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>>;
+
+    fn next_child(&self, after: Option<u8>) -> Option<(u8, &Child<'a, T, S>)>;
+
+    fn prev_child(&self, before: Option<u8>) -> Option<(u8, &Child<'a, T, S>)>;
+
+    /// Visit all children in the order of their keys' bytes, the terminator's included.
+    fn for_each_child(&self, f: &mut dyn FnMut(u8, &Child<'a, T, S>));
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>>;
+
+    fn children_mut(&mut self) -> Vec<(u8, &mut Child<'a, T, S>)>;
+
+    fn into_children(self: Box<Self>) -> Vec<(u8, Box<Child<'a, T, S>>)>;
+
+    fn nb_children(&self) -> usize;
+
+    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a>;
+
+    fn should_downgrade(&self) -> bool;
+
+    fn downgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a>;
+
+    fn clone_box(&self) -> Box<dyn NodeImpl<'a, T, S> + 'a>
+        where T: Clone,
+              S: Clone;
+}
+
+impl<'a, T: Clone, S: Clone> Clone for Node<'a, T, S> {
+    fn clone(&self) -> Self {
+        Node {
+            prefix: self.prefix.clone(),
+            inner: self.inner.clone_box(),
+            #[cfg(feature = "counts")]
+            count: self.count,
+            summary: self.summary.clone(),
+        }
+    }
+}
+
+impl<'a, T, S: Summary<T>> Node<'a, T, S> {
+    fn new(prefix: Vec<u8>) -> Self {
+        Node {
+            prefix,
+            inner: Box::new(DefaultNode::default()),
+            #[cfg(feature = "counts")]
+            count: 0,
+            summary: S::default(),
+        }
+    }
+
+    #[cfg(feature = "counts")]
+    fn grow(&mut self, count: usize) {
+        self.count += count;
+    }
+
+    #[cfg(not(feature = "counts"))]
+    fn grow(&mut self, _: usize) {}
+
+    #[cfg(feature = "counts")]
+    fn shrink(&mut self, count: usize) {
+        self.count -= count;
+    }
+
+    #[cfg(not(feature = "counts"))]
+    fn shrink(&mut self, _: usize) {}
+
+    /// Refresh the summary of the entries below the node from its children's.
+    fn refresh(&mut self, term: u8) {
+        // Zero-sized summaries, such as `()`, have nothing to refresh
+        if mem::size_of::<S>() == 0 {
+            return;
+        }
+
+        // The terminator's child comes first in key order, wherever its byte falls
+        let mut summary = match self.find_child(term) {
+            Some(child) => child.summarize(S::default()),
+            None        => S::default(),
+        };
+        self.inner.for_each_child(&mut |key, child| {
+            if key != term {
+                summary = child.summarize(mem::take(&mut summary));
+            }
+        });
+        self.summary = summary;
+    }
+
+    /// Combine `summary` with the summaries of the entries below the node with a key (relative to
+    /// the node's prefix) within the given bounds.
+    fn aggregate(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut summary: S, term: u8) -> S {
+        if let (Bound::Unbounded, Bound::Unbounded) = (start, end) {
+            return summary.combine(&self.summary);
+        }
+
+        let mut next = self.next_child(None, term);
+        while let Some((key, child)) = next {
+            // The terminator's child holds the key ending here, which the bounds apply to as is
+            let bounds = if key == term {
+                Some((start, end))
+            } else {
+                narrow_bound(start, &[key], true).zip(narrow_bound(end, &[key], false))
+            };
+            if let Some((start, end)) = bounds {
+                summary = child.aggregate(start, end, summary, term);
+            }
+            next = self.next_child(Some(key), term);
+        }
+        summary
+    }
+
+    /// Sum the number of entries below each child.
+    #[cfg(not(feature = "counts"))]
+    fn count_children(&self) -> usize {
+        let mut count = 0;
+        let mut next = self.inner.next_child(None);
+        while let Some((key, child)) = next {
+            count += child.count();
+            next = self.inner.next_child(Some(key));
+        }
+        count
+    }
+
+    fn insert(&mut self, key: &[u8], value: T, term: u8) -> Option<T> {
+        let matched = common_prefix_len(&self.prefix, key);
+        if matched < self.prefix.len() {
+            self.split_prefix(matched);
+        }
+
+        let (byte, rest) = branch(&key[matched..], term);
+        let replaced = match self.find_child_mut(byte) {
+            Some(child) => child.insert(rest, value, term),
+            None        => {
+                self.insert_child(byte, Child::Leaf(rest.to_vec(), value));
+                None
+            }
+        };
+        if replaced.is_none() {
+            self.grow(1);
+        }
+        self.refresh(term);
+        replaced
+    }
+
+    /// See `Child::insert_new`.
+    fn insert_new(&mut self, key: &[u8], value: T, term: u8) -> &mut T {
+        let matched = common_prefix_len(&self.prefix, key);
+        if matched < self.prefix.len() {
+            self.split_prefix(matched);
+        }
+
+        let (byte, rest) = branch(&key[matched..], term);
+        self.insert_child(byte, Child::Leaf(rest.to_vec(), value));
+        self.grow(1);
+        self.refresh(term);
+        match self.find_child_mut(byte) {
+            Some(Child::Leaf(_, value)) => value,
+            _                           => unreachable!(),
+        }
+    }
+
+    /// See `Child::update`, refreshing the summary if the key is there.
+    fn update<F>(&mut self, key: &[u8], value: T, term: u8, resolve: &mut F) -> Result<(), T>
+        where F: FnMut(&mut T, T) + ?Sized
+    {
+        let key = match key.strip_prefix(&self.prefix[..]) {
+            Some(key) => key,
+            None      => return Err(value),
+        };
+        let (byte, rest) = branch(key, term);
+        match self.find_child_mut(byte) {
+            Some(child) => child.update(rest, value, term, resolve)?,
+            None        => return Err(value),
+        }
+        self.refresh(term);
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8], term: u8) -> Option<&T> {
+        let key = key.strip_prefix(&self.prefix[..])?;
+        let (byte, rest) = branch(key, term);
+        self.find_child(byte)?.get(rest, term)
+    }
+
+    fn get_mut(&mut self, key: &[u8], term: u8) -> Option<&mut T> {
+        let key = key.strip_prefix(&self.prefix[..])?;
+        let (byte, rest) = branch(key, term);
+        self.find_child_mut(byte)?.get_mut(rest, term)
+    }
+
+    fn longest_prefix(&self, key: &[u8], term: u8) -> Option<(usize, &T)> {
+        let key = key.strip_prefix(&self.prefix[..])?;
+        let depth = self.prefix.len();
+
+        // Prefer the longest match found further down, if any
+        let longer = key.split_first()
+            .and_then(|(&byte, rest)| self.find_child(byte)?.longest_prefix(rest, term))
+            .map(|(len, value)| (depth + 1 + len, value));
+
+        longer.or_else(|| match self.find_child(term) {
+            Some(Child::Leaf(_, value)) => Some((depth, value)),
+            _                           => None,
+        })
+    }
+
+    fn remove(&mut self, key: &[u8], term: u8) -> Option<T> {
+        let key = key.strip_prefix(&self.prefix[..])?;
+        let (byte, rest) = branch(key, term);
+        let child = self.find_child_mut(byte)?;
+        let removed = match child {
+            Child::Leaf(suffix, _) => {
+                if suffix[..] != *rest {
+                    return None;
+                }
+                self.remove_child(byte).and_then(Child::into_leaf)
+            }
+            Child::Node(node) => {
+                let removed = node.remove(rest, term);
+                if removed.is_some() {
+                    child.compress(term);
+                }
+                removed
+            }
+        };
+        if removed.is_some() {
+            self.shrink(1);
+            self.refresh(term);
+        }
+        removed
+    }
+
+    /// Keep the entries below this node for which `f` returns true, appending their keys to `key`
+    /// and counting the others in `removed`. Returns whether any entry is left.
+    fn retain<F>(&mut self, key: &mut Vec<u8>, f: &mut F, term: u8, removed: &mut usize) -> bool
+        where F: FnMut(&[u8], &mut T) -> bool
+    {
+        key.extend_from_slice(&self.prefix);
+        let depth = key.len();
+        let removed_before = *removed;
+
+        // Removing children may change the node's layout, so gather their keys first, in key order
+        let mut bytes = [0; 256];
+        let mut nb_children = 0;
+        self.inner.for_each_child(&mut |byte, _| {
+            bytes[nb_children] = byte;
+            nb_children += 1;
+        });
+        let bytes = &mut bytes[..nb_children];
+        if let Some(index) = bytes.iter().position(|&byte| byte == term) {
+            bytes[..=index].rotate_right(1);
+        }
+
+        for &byte in bytes.iter() {
+            key.truncate(depth);
+            if byte != term {
+                key.push(byte);
+            }
+            if !self.find_child_mut(byte).unwrap().retain(key, f, term, removed) {
+                self.remove_child(byte);
+            }
+        }
+        self.shrink(*removed - removed_before);
+        self.refresh(term);
+        self.inner.nb_children() > 0
+    }
+
+    /// Detach the child subtree holding all keys starting with the given prefix, which doesn't
+    /// hold all of this node's keys, along with the length of the part of the prefix leading to it.
+    fn remove_prefix(&mut self, prefix: &[u8], term: u8) -> Option<(Child<'a, T, S>, usize)> {
+        let rest = prefix.strip_prefix(&self.prefix[..])?;
+        let (&byte, rest) = rest.split_first()?;
+        let depth = self.prefix.len() + 1;
+
+        let child = self.find_child_mut(byte)?;
+        let (subtree, len) = if child.holds_prefix(rest) {
+            (self.remove_child(byte).unwrap(), 0)
+        } else {
+            let detached = child.as_node_mut()?.remove_prefix(rest, term)?;
+            child.compress(term);
+            detached
+        };
+
+        #[cfg(feature = "counts")]
+        self.shrink(subtree.count());
+        self.refresh(term);
+
+        Some((subtree, depth + len))
+    }
+
+    /// Split the subtree into the entries with keys (relative to the node) below the given one
+    /// and the others, returning both halves and how many entries were moved to the second.
+    fn split(mut self, key: &[u8], term: u8) -> (Halves<'a, T, S>, usize) {
+        let matched = common_prefix_len(&self.prefix, key);
+        if matched < self.prefix.len() {
+            // All keys of the subtree compare to the key the same way its prefix does
+            let below = key.get(matched).is_some_and(|&byte| self.prefix[matched] < byte);
+            let child = Child::Node(self);
+            return if below { ((Some(child), None), 0) } else { child.split_above() };
+        }
+
+        let (&byte, rest) = match key[matched..].split_first() {
+            Some(split) => split,
+            None        => return Child::Node(self).split_above(),
+        };
+
+        // Move the children after the key's branch, the terminator's one always being before it
+        let mut above = Node::new(self.prefix.clone());
+        let mut keys = Vec::new();
+        let mut next = self.inner.next_child(Some(byte));
+        while let Some((key, _)) = next {
+            if key != term {
+                keys.push(key);
+            }
+            next = self.inner.next_child(Some(key));
+        }
+        let mut moved = 0;
+        for key in keys {
+            let child = self.remove_child(key).unwrap();
+            moved += child.count();
+            above.insert_child(key, child);
+        }
+
+        if let Some(child) = self.remove_child(byte) {
+            let ((child_below, child_above), count) = child.split(rest, term);
+            moved += count;
+            if let Some(child) = child_below {
+                self.insert_child(byte, child);
+            }
+            if let Some(child) = child_above {
+                above.insert_child(byte, child);
+            }
+        }
+
+        self.shrink(moved);
+        above.grow(moved);
+        self.refresh(term);
+        above.refresh(term);
+
+        ((self.into_child(term), above.into_child(term)), moved)
+    }
+
+    /// Wrap the node in a child, collapsing it if it has less than two children.
+    fn into_child(self, term: u8) -> Option<Child<'a, T, S>> {
+        if self.inner.nb_children() == 0 {
+            return None;
+        }
+        let mut child = Child::Node(self);
+        child.compress(term);
+        Some(child)
+    }
+
+    /// Remove the first or last entry below this node, appending its key to `key`.
+    fn pop(&mut self, last: bool, key: &mut Vec<u8>, term: u8) -> T {
+        key.extend_from_slice(&self.prefix);
+        let (byte, _) = self.edge_child(last, term);
+        if byte != term {
+            key.push(byte);
+        }
+
+        let child = self.find_child_mut(byte).unwrap();
+        let value = match child {
+            Child::Leaf(suffix, _) => {
+                key.extend_from_slice(suffix);
+                self.remove_child(byte).and_then(Child::into_leaf).unwrap()
+            }
+            Child::Node(node) => {
+                let value = node.pop(last, key, term);
+                child.compress(term);
+                value
+            }
+        };
+        self.shrink(1);
+        self.refresh(term);
+        value
+    }
+
+    /// Move the node's prefix after `len` bytes into a new child node, leaving the summary to be
+    /// refreshed.
+    fn split_prefix(&mut self, len: usize) {
+        let prefix = self.prefix[..len].to_vec();
+        let mut child = mem::replace(self, Node::new(prefix));
+        #[cfg(feature = "counts")] {
+            self.count = child.count;
+        }
+        let key = child.prefix[len];
+        child.prefix.drain(..=len);
+        self.insert_child(key, Child::Node(child));
+    }
+
+    fn insert_child(&mut self, key: u8, child: Child<'a, T, S>) -> Option<Child<'a, T, S>> {
+        let result = self.inner.insert_child(key, child);
+        match result {
+            Ok(replaced_child) => replaced_child,
+            Err(child)         => {
+                self.upgrade();
+                self.insert_child(key, child)
+            }
+        }
+    }
+
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>> {
+        self.inner.find_child(key)
+    }
+
+    /// Find the first child after the given one, in key order: the terminator's child comes first,
+    /// since it ends a key which is a prefix of all the others.
+    fn next_child(&self, after: Option<u8>, term: u8) -> Option<(u8, &Child<'a, T, S>)> {
+        let after = match after {
+            None => {
+                if let Some(child) = self.find_child(term) {
+                    return Some((term, child));
+                }
+                None
+            }
+            Some(key) if key == term => None,
+            Some(key)                => Some(key),
+        };
+
+        match self.inner.next_child(after)? {
+            (key, _) if key == term => self.inner.next_child(Some(term)),
+            next                    => Some(next),
+        }
+    }
+
+    /// Find the last child before the given one, in key order.
+    fn prev_child(&self, before: Option<u8>, term: u8) -> Option<(u8, &Child<'a, T, S>)> {
+        if before == Some(term) {
+            return None;
+        }
+
+        let prev = match self.inner.prev_child(before) {
+            Some((key, _)) if key == term => self.inner.prev_child(Some(term)),
+            prev                          => prev,
+        };
+        prev.or_else(|| self.find_child(term).map(|child| (term, child)))
+    }
+
+    fn edge_child(&self, last: bool, term: u8) -> (u8, &Child<'a, T, S>) {
+        let child = if last { self.prev_child(None, term) } else { self.next_child(None, term) };
+        child.unwrap()
+    }
+
+    /// Borrow all children mutably, in key order.
+    fn children_mut(&mut self, term: u8) -> Vec<(u8, &mut Child<'a, T, S>)> {
+        let mut children = self.inner.children_mut();
+        children.sort_unstable_by_key(|&(key, _)| (key != term, key));
+        children
+    }
+
+    /// Take all children, in key order.
+    fn into_children(self, term: u8) -> Vec<(u8, Box<Child<'a, T, S>>)> {
+        let mut children = self.inner.into_children();
+        children.sort_unstable_by_key(|&(key, _)| (key != term, key));
+        children
+    }
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>> {
+        let removed = self.inner.remove_child(key);
+        if self.inner.should_downgrade() {
+            self.downgrade();
+        }
+        removed
+    }
+
+    fn upgrade(&mut self) {
+        take_mut::take(&mut self.inner, NodeImpl::upgrade);
+    }
+
+    fn downgrade(&mut self) {
+        take_mut::take(&mut self.inner, NodeImpl::downgrade);
+    }
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>> {
+        self.inner.find_child_mut(key)
+    }
+}
+
+fn bound_contains(bound: Bound<&[u8]>, byte: u8) -> bool {
+    match bound {
+        Bound::Included(key) | Bound::Excluded(key) => key.contains(&byte),
+        Bound::Unbounded                            => false,
+    }
+}
+
+/// Narrow a bound down to the keys starting with the given bytes, returning the bound left on the
+/// rest of them, or `None` if none of them are within it.
+fn narrow_bound<'k>(bound: Bound<&'k [u8]>, bytes: &[u8], lower: bool) -> Option<Bound<&'k [u8]>> {
+    let key = match bound {
+        Bound::Included(key) | Bound::Excluded(key) => key,
+        Bound::Unbounded                            => return Some(Bound::Unbounded),
+    };
+
+    let matched = common_prefix_len(bytes, key);
+    if matched == bytes.len() {
+        let rest = &key[matched..];
+        return Some(match bound {
+            Bound::Included(_) => Bound::Included(rest),
+            _                  => Bound::Excluded(rest),
+        });
+    }
+
+    // The keys all compare to the bound the same way the bytes do
+    let above = key.get(matched).map_or(true, |&byte| bytes[matched] > byte);
+    (above == lower).then_some(Bound::Unbounded)
+}
+
+/// Whether the empty key is within a bound.
+fn bound_admits_empty(bound: Bound<&[u8]>, lower: bool) -> bool {
+    match bound {
+        Bound::Included(key) => !lower || key.is_empty(),
+        Bound::Excluded(key) => !lower && !key.is_empty(),
+        Bound::Unbounded     => true,
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Split a key into the byte used to find the next child and the rest of the key, the terminator
+/// standing for the end of the key.
+fn branch(key: &[u8], term: u8) -> (u8, &[u8]) {
+    match key.split_first() {
+        Some((&byte, rest)) => (byte, rest),
+        None                => (term, &[]),
+    }
+}
+
+/// The entries below and above a key, see `Node::split`.
+type Halves<'a, T, S> = (Option<Child<'a, T, S>>, Option<Child<'a, T, S>>);
+
+#[derive(Clone)]
+enum Child<'a, T: 'a, S: 'a> {
+    Node(Node<'a, T, S>),
+    Leaf(Vec<u8>, T),
+}
+
+impl<'a, T, S: Summary<T>> Child<'a, T, S> {
+    fn insert(&mut self, key: &[u8], value: T, term: u8) -> Option<T> {
+        match self {
+            Child::Node(node)                                  => node.insert(key, value, term),
+            Child::Leaf(suffix, existing) if suffix[..] == *key => Some(mem::replace(existing, value)),
+            Child::Leaf(..)                                    => {
+                self.expand(key, term);
+                self.as_node_mut().unwrap().insert(key, value, term);
+                None
+            }
+        }
+    }
+
+    fn get(&self, key: &[u8], term: u8) -> Option<&T> {
+        match self {
+            Child::Node(node)                               => node.get(key, term),
+            Child::Leaf(suffix, value) if suffix[..] == *key => Some(value),
+            Child::Leaf(..)                                 => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &[u8], term: u8) -> Option<&mut T> {
+        match self {
+            Child::Node(node)                               => node.get_mut(key, term),
+            Child::Leaf(suffix, value) if suffix[..] == *key => Some(value),
+            Child::Leaf(..)                                 => None,
+        }
+    }
+
+    /// Find the value of a key relative to the child past its first `skip` bytes, see `SubTrie`.
+    fn get_past(&self, skip: usize, key: &[u8], term: u8) -> Option<&T> {
+        match self {
+            Child::Leaf(suffix, value) => (suffix[skip..] == *key).then_some(value),
+            Child::Node(node)          => {
+                let key = key.strip_prefix(&node.prefix[skip..])?;
+                let (byte, rest) = branch(key, term);
+                node.find_child(byte)?.get(rest, term)
+            }
+        }
+    }
+
+    fn get_mut_past(&mut self, skip: usize, key: &[u8], term: u8) -> Option<&mut T> {
+        match self {
+            Child::Leaf(suffix, value) => (suffix[skip..] == *key).then_some(value),
+            Child::Node(node)          => {
+                let key = key.strip_prefix(&node.prefix[skip..])?;
+                let (byte, rest) = branch(key, term);
+                node.find_child_mut(byte)?.get_mut(rest, term)
+            }
+        }
+    }
+
+    fn longest_prefix(&self, key: &[u8], term: u8) -> Option<(usize, &T)> {
+        match self {
+            Child::Node(node)          => node.longest_prefix(key, term),
+            Child::Leaf(suffix, value) => key.starts_with(suffix).then_some((suffix.len(), value)),
+        }
+    }
+
+    /// Keep the entries below this child for which `f` returns true, see `Node::retain`.
+    fn retain<F>(&mut self, key: &mut Vec<u8>, f: &mut F, term: u8, removed: &mut usize) -> bool
+        where F: FnMut(&[u8], &mut T) -> bool
+    {
+        match self {
+            Child::Leaf(suffix, value) => {
+                key.extend_from_slice(suffix);
+                let keep = f(key, value);
+                if !keep {
+                    *removed += 1;
+                }
+                keep
+            }
+            Child::Node(node) => {
+                let keep = node.retain(key, f, term, removed);
+                if keep {
+                    self.compress(term);
+                }
+                keep
+            }
+        }
+    }
+
+    /// Split the subtree into the entries with keys (relative to the child) below the given one
+    /// and the others, returning both halves and how many entries were moved to the second.
+    fn split(self, key: &[u8], term: u8) -> (Halves<'a, T, S>, usize) {
+        match self {
+            Child::Node(node)                               => node.split(key, term),
+            Child::Leaf(ref suffix, _) if suffix[..] < *key => ((Some(self), None), 0),
+            Child::Leaf(..)                                 => ((None, Some(self)), 1),
+        }
+    }
+
+    /// Move the whole subtree to the second half of a split.
+    fn split_above(self) -> (Halves<'a, T, S>, usize) {
+        let moved = self.count();
+        ((None, Some(self)), moved)
+    }
+
+    /// Move all entries of another subtree at the same position into this one, returning how
+    /// many of them had a key already there, whose value `resolve` updates with the moved one.
+    fn merge<F>(&mut self, other: Self, term: u8, resolve: &mut F) -> usize
+        where F: FnMut(&mut T, T) + ?Sized
+    {
+        let mut other = match other {
+            Child::Leaf(suffix, value) => return self.upsert(&suffix, value, term, resolve) as usize,
+            Child::Node(other)         => other,
+        };
+
+        if let Child::Leaf(..) = self {
+            // Keep the other subtree and add this entry to it, the moved value being the one
+            // already there if it holds its key
+            if let Child::Leaf(suffix, value) = mem::replace(self, Child::Node(other)) {
+                let mut resolve = |slot: &mut T, existing| {
+                    let moved = mem::replace(slot, existing);
+                    resolve(slot, moved);
+                };
+                return self.upsert(&suffix, value, term, &mut resolve) as usize;
+            }
+            unreachable!();
+        }
+
+        #[cfg(feature = "counts")]
+        let added = other.count;
+
+        // Bring both nodes to the same prefix, so that their children can be merged
+        let node = self.as_node_mut().unwrap();
+        let matched = common_prefix_len(&node.prefix, &other.prefix);
+        if matched < node.prefix.len() {
+            node.split_prefix(matched);
+        }
+        if matched < other.prefix.len() {
+            other.split_prefix(matched);
+        }
+
+        let mut replaced = 0;
+        for (key, child) in other.inner.into_children() {
+            match node.find_child_mut(key) {
+                Some(existing) => replaced += existing.merge(*child, term, resolve),
+                None           => {
+                    node.insert_child(key, *child);
+                }
+            }
+        }
+
+        #[cfg(feature = "counts")]
+        node.grow(added - replaced);
+        node.refresh(term);
+
+        replaced
+    }
+
+    /// Replace the first `len` bytes of the keys below this child with the given ones.
+    fn rekey(&mut self, len: usize, prefix: &[u8]) {
+        let bytes = match self {
+            Child::Leaf(suffix, _) => suffix,
+            Child::Node(node)      => &mut node.prefix,
+        };
+        bytes.splice(..len, prefix.iter().cloned());
+    }
+
+    /// Insert an entry, or update the value of its key with `resolve` if it's already there,
+    /// returning whether it was.
+    fn upsert<F>(&mut self, key: &[u8], value: T, term: u8, resolve: &mut F) -> bool
+        where F: FnMut(&mut T, T) + ?Sized
+    {
+        match self.update(key, value, term, resolve) {
+            Ok(())     => true,
+            Err(value) => {
+                self.insert(key, value, term);
+                false
+            }
+        }
+    }
+
+    /// Update the value of a key with `resolve`, handing the value back if the key isn't there.
+    fn update<F>(&mut self, key: &[u8], value: T, term: u8, resolve: &mut F) -> Result<(), T>
+        where F: FnMut(&mut T, T) + ?Sized
+    {
+        match self {
+            Child::Node(node)                                  => node.update(key, value, term, resolve),
+            Child::Leaf(suffix, existing) if suffix[..] == *key => {
+                resolve(existing, value);
+                Ok(())
+            }
+            Child::Leaf(..)                                    => Err(value),
+        }
+    }
+
+    /// Count the entries below this child with a key (relative to the child) less than the given
+    /// one.
+    #[cfg(feature = "counts")]
+    fn rank(&self, key: &[u8], term: u8) -> usize {
+        let node = match self {
+            Child::Leaf(suffix, _) => return (suffix[..] < *key) as usize,
+            Child::Node(node)      => node,
+        };
+
+        let matched = common_prefix_len(&node.prefix, key);
+        if matched < node.prefix.len() {
+            // All keys of the subtree compare to the key the same way its prefix does
+            let below = key.get(matched).is_some_and(|&byte| node.prefix[matched] < byte);
+            return if below { node.count } else { 0 };
+        }
+
+        let (&byte, rest) = match key[matched..].split_first() {
+            Some(split) => split,
+            None        => return 0,
+        };
+
+        // The terminator's child is always before the key's branch
+        let mut rank = node.find_child(term).map_or(0, Child::count);
+        let mut next = node.inner.next_child(None);
+        while let Some((key, child)) = next {
+            if key > byte {
+                break;
+            }
+            if key == byte {
+                rank += child.rank(rest, term);
+            } else if key != term {
+                rank += child.count();
+            }
+            next = node.inner.next_child(Some(key));
+        }
+        rank
+    }
+
+    /// Find the n-th entry below this child, appending its key to `key`.
+    #[cfg(feature = "counts")]
+    fn select(&self, mut n: usize, key: &mut Vec<u8>, term: u8) -> &T {
+        let mut child = self;
+        loop {
+            let node = match child {
+                Child::Leaf(suffix, value) => {
+                    key.extend_from_slice(suffix);
+                    return value;
+                }
+                Child::Node(node) => node,
+            };
+
+            key.extend_from_slice(&node.prefix);
+            let mut next = node.next_child(None, term);
+            while let Some((byte, next_child)) = next {
+                let count = next_child.count();
+                if n < count {
+                    if byte != term {
+                        key.push(byte);
+                    }
+                    child = next_child;
+                    break;
+                }
+                n -= count;
+                next = node.next_child(Some(byte), term);
+            }
+        }
+    }
+
+    /// Combine `summary` with the summary of all entries below this child.
+    fn summarize(&self, summary: S) -> S {
+        match self {
+            Child::Leaf(_, value) => summary.combine(&S::from_value(value)),
+            Child::Node(node)     => summary.combine(&node.summary),
+        }
+    }
+
+    /// Combine `summary` with the summaries of the entries below this child with a key (relative
+    /// to the child) within the given bounds.
+    fn aggregate(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, summary: S, term: u8) -> S {
+        let bytes = match self {
+            Child::Leaf(suffix, _) => suffix,
+            Child::Node(node)      => &node.prefix,
+        };
+        let (start, end) = match (narrow_bound(start, bytes, true), narrow_bound(end, bytes, false)) {
+            (Some(start), Some(end)) => (start, end),
+            _                        => return summary,
+        };
+
+        match self {
+            Child::Leaf(_, value) if bound_admits_empty(start, true) && bound_admits_empty(end, false) => {
+                summary.combine(&S::from_value(value))
+            }
+            Child::Leaf(..)   => summary,
+            Child::Node(node) => node.aggregate(start, end, summary, term),
+        }
+    }
+
+    /// Whether all keys below this child start with the given prefix.
+    fn holds_prefix(&self, prefix: &[u8]) -> bool {
+        match self {
+            Child::Leaf(suffix, _) => suffix.starts_with(prefix),
+            Child::Node(node)      => node.prefix.starts_with(prefix),
+        }
+    }
+
+    /// Count the entries below this child.
+    fn count(&self) -> usize {
+        match self {
+            Child::Leaf(..)   => 1,
+            #[cfg(feature = "counts")]
+            Child::Node(node) => node.count,
+            #[cfg(not(feature = "counts"))]
+            Child::Node(node) => node.count_children(),
+        }
+    }
+
+    /// Find the first or last entry below this child, appending its key to `key`.
+    fn edge(&self, last: bool, key: &mut Vec<u8>, term: u8) -> &T {
+        let mut child = self;
+        loop {
+            match child {
+                Child::Leaf(suffix, value) => {
+                    key.extend_from_slice(suffix);
+                    return value;
+                }
+                Child::Node(node) => {
+                    key.extend_from_slice(&node.prefix);
+                    let (byte, next) = node.edge_child(last, term);
+                    if byte != term {
+                        key.push(byte);
+                    }
+                    child = next;
+                }
+            }
+        }
+    }
+
+    /// Find the subtree holding all keys starting with the given prefix, along with the length of
+    /// the part of the prefix leading to it.
+    fn find_prefix(&self, prefix: &[u8]) -> Option<(&Self, usize)> {
+        let node = match self {
+            Child::Node(node) => node,
+            Child::Leaf(suffix, _) => {
+                return suffix.starts_with(prefix).then_some((self, 0));
+            }
+        };
+
+        if prefix.len() <= node.prefix.len() {
+            return node.prefix.starts_with(prefix).then_some((self, 0));
+        }
+
+        let rest = prefix.strip_prefix(&node.prefix[..])?;
+        let (subtree, depth) = node.find_child(rest[0])?.find_prefix(&rest[1..])?;
+        Some((subtree, node.prefix.len() + 1 + depth))
+    }
+
+    /// Turn a leaf into an inner node holding its entry, only branching where its key diverges
+    /// from the given one.
+    fn expand(&mut self, key: &[u8], term: u8) {
+        let matched = match self {
+            Child::Leaf(suffix, _) => common_prefix_len(suffix, key),
+            Child::Node(_)         => unreachable!(),
+        };
+
+        let node = Child::Node(Node::new(key[..matched].to_vec()));
+        if let Child::Leaf(suffix, existing) = mem::replace(self, node) {
+            self.as_node_mut().unwrap().insert(&suffix, existing, term);
+        }
+    }
+
+    /// Insert an entry whose key diverges from all keys below this child right at it, returning
+    /// its value.
+    fn insert_new(&mut self, key: &[u8], value: T, term: u8) -> &mut T {
+        if let Child::Leaf(..) = self {
+            self.expand(key, term);
+        }
+        self.as_node_mut().unwrap().insert_new(key, value, term)
+    }
+
+    /// Collapse an inner node left with a single child, merging their keys.
+    fn compress(&mut self, term: u8) {
+        let node = match self {
+            Child::Node(node) if node.inner.nb_children() == 1 => node,
+            _                                                  => return,
+        };
+
+        let (key, _) = node.inner.next_child(None).unwrap();
+        let child = node.inner.remove_child(key).unwrap();
+
+        let mut prefix = mem::take(&mut node.prefix);
+        if key != term {
+            prefix.push(key);
+        }
+
+        *self = match child {
+            Child::Leaf(suffix, value) => {
+                prefix.extend(suffix);
+                Child::Leaf(prefix, value)
+            }
+            Child::Node(mut node) => {
+                prefix.extend(node.prefix);
+                node.prefix = prefix;
+                Child::Node(node)
+            }
+        };
+    }
+
+    fn as_node_mut(&mut self) -> Option<&mut Node<'a, T, S>> {
+        if let Child::Node(ref mut node) = self {
+            Some(node)
+        } else {
+            None
+        }
+    }
+
+    fn into_leaf(self) -> Option<T> {
+        if let Child::Leaf(_, value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    #[test]
+    fn test_readme_insert_lookup_example() {
+        let mut map = Trie::for_utf8();
+        map.insert(b"a", 0).unwrap();
+        map.insert(b"ac", 1).unwrap();
+
+        assert_eq!(map.get(b"a").unwrap(), Some(&0));
+        assert_eq!(map.get(b"ac").unwrap(), Some(&1));
+        assert_eq!(map.get(b"ab").unwrap(), None);
+    }
+
+    #[test]
+    fn test_readme_removal_example() {
+        let mut map = Trie::for_utf8();
+        map.insert(b"a", 0).unwrap();
+        map.insert(b"ac", 1).unwrap();
+
+        assert_eq!(map.remove(b"a").unwrap(), Some(0));
+        assert_eq!(map.remove(b"a").unwrap(), None);
+        assert_eq!(map.get(b"ac").unwrap(), Some(&1));
+    }
+
+    #[test]
+    fn test_readme_iteration_example() {
+        let mut map = Trie::for_utf8();
+        map.insert(b"b", 2).unwrap();
+        map.insert(b"ab", 1).unwrap();
+        map.insert(b"a", 0).unwrap();
+
+        let keys: Vec<_> = map.keys().collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"ab".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn test_readme_range_example() {
+        let mut map = Trie::for_utf8();
+        map.insert(b"user:099", 0).unwrap();
+        map.insert(b"user:100", 1).unwrap();
+        map.insert(b"user:150", 2).unwrap();
+
+        let values: Vec<_> = map.range(b"user:100".as_ref()..).unwrap()
+            .rev()
+            .map(|(_, value)| *value)
+            .collect();
+        assert_eq!(values, vec![2, 1]);
+    }
+
+    trait TrieTestExtensions<T: Clone + PartialEq + Debug> {
+        fn check_insertion(&mut self, key: &[u8], value: T);
+
+        fn check_existence(&mut self, key: &[u8], value: T);
+    }
+
+    impl<'a, T: 'a + Clone + PartialEq + Debug> TrieTestExtensions<T> for Trie<'a, T> {
+        fn check_insertion(&mut self, key: &[u8], value: T) {
+            self.insert(key, value.clone()).unwrap();
+            self.check_existence(key, value);
+        }
+
+        fn check_existence(&mut self, key: &[u8], value: T) {
+            assert_eq!(self.get(key).unwrap(), Some(&value));
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let mut trie = Trie::for_utf8();
+        trie.check_insertion(b"the answer", 42);
+    }
+
+    #[test]
+    fn it_works_for_empty_strings() {
+        let mut trie = Trie::for_utf8();
+        trie.check_insertion(b"", 1);
+    }
+
+    #[test]
+    fn it_is_empty_by_default() {
+        let trie = Trie::<()>::for_utf8();
+        assert!(trie.is_empty());
+        assert_eq!(trie.len(), 0);
+    }
+
+    #[test]
+    fn it_counts_entries() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"a", 1).unwrap();
+        trie.insert(b"ab", 2).unwrap();
+        trie.insert(b"ab", 3).unwrap();
+        *trie.entry(b"abc").unwrap().or_insert(4) += 1;
+        *trie.entry(b"abc").unwrap().or_insert(4) += 1;
+        assert_eq!(trie.len(), 3);
+
+        assert_eq!(trie.remove(b"ab").unwrap(), Some(3));
+        assert_eq!(trie.remove(b"ab").unwrap(), None);
+        assert_eq!(trie.len(), 2);
+    }
+
+    #[test]
+    fn it_doesnt_overwrite_entries_with_a_common_prefix() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"a", 1).unwrap();
+        trie.insert(b"ab", 2).unwrap();
+        assert_eq!(trie.get(b"a").unwrap(), Some(&1));
+        assert_eq!(trie.get(b"ab").unwrap(), Some(&2));
+    }
+
+    #[test]
+    fn it_can_store_more_than_4_parallel_entries() {
+        let mut trie = Trie::for_utf8();
+        // 1) insert
+        trie.check_insertion(b"a", 1);
+        trie.check_insertion(b"b", 2);
+        trie.check_insertion(b"c", 3);
+        trie.check_insertion(b"d", 4);
+        trie.check_insertion(b"e", 5);
+        // 2) verify
+        trie.check_existence(b"a", 1);
+        trie.check_existence(b"b", 2);
+        trie.check_existence(b"c", 3);
+        trie.check_existence(b"d", 4);
+        trie.check_existence(b"e", 5);
+    }
+
+    #[test]
+    fn it_can_store_more_than_16_parallel_entries() {
+        let mut trie = Trie::for_utf8();
+        // 1) insert
+        trie.check_insertion(b"a", 1);
+        trie.check_insertion(b"c", 2);
+        trie.check_insertion(b"d", 3);
+        trie.check_insertion(b"e", 4);
+        trie.check_insertion(b"f", 5);
+        trie.check_insertion(b"g", 6);
+        trie.check_insertion(b"h", 7);
+        trie.check_insertion(b"i", 8);
+        trie.check_insertion(b"j", 9);
+        trie.check_insertion(b"k", 10);
+        trie.check_insertion(b"l", 11);
+        trie.check_insertion(b"m", 12);
+        trie.check_insertion(b"n", 13);
+        trie.check_insertion(b"o", 14);
+        trie.check_insertion(b"p", 15);
+        trie.check_insertion(b"q", 16);
+        trie.check_insertion(b"r", 17);
+        // 2) verify
+        trie.check_existence(b"a", 1);
+        trie.check_existence(b"c", 2);
+        trie.check_existence(b"d", 3);
+        trie.check_existence(b"e", 4);
+        trie.check_existence(b"f", 5);
+        trie.check_existence(b"g", 6);
+        trie.check_existence(b"h", 7);
+        trie.check_existence(b"i", 8);
+        trie.check_existence(b"j", 9);
+        trie.check_existence(b"k", 10);
+        trie.check_existence(b"l", 11);
+        trie.check_existence(b"m", 12);
+        trie.check_existence(b"n", 13);
+        trie.check_existence(b"o", 14);
+        trie.check_existence(b"p", 15);
+        trie.check_existence(b"q", 16);
+        trie.check_existence(b"r", 17);
+    }
+
+    #[test]
+    fn it_removes_entries() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"a", 1).unwrap();
+        trie.insert(b"ab", 2).unwrap();
+        trie.insert(b"abc", 3).unwrap();
+
+        assert_eq!(trie.remove(b"ab").unwrap(), Some(2));
+        assert_eq!(trie.remove(b"ab").unwrap(), None);
+        assert_eq!(trie.get(b"ab").unwrap(), None);
+        trie.check_existence(b"a", 1);
+        trie.check_existence(b"abc", 3);
+
+        assert_eq!(trie.remove(b"abcd").unwrap(), None);
+        assert_eq!(trie.remove(b"b").unwrap(), None);
+    }
+
+    #[test]
+    fn it_is_empty_after_removing_every_entry() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"abc", 1).unwrap();
+        trie.insert(b"", 2).unwrap();
+
+        assert_eq!(trie.remove(b"abc").unwrap(), Some(1));
+        assert!(!trie.is_empty());
+        assert_eq!(trie.remove(b"").unwrap(), Some(2));
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn it_rejects_removing_keys_containing_the_terminator() {
+        let mut trie = Trie::<()>::for_ascii();
+        assert!(trie.remove(b"a\0").is_err());
+    }
+
+    #[test]
+    fn it_shrinks_nodes_after_removing_parallel_entries() {
+        let mut trie = Trie::for_utf8();
+        // 1) grow the root up to its largest node type
+        for byte in 0..0xff {
+            trie.check_insertion(&[byte, byte], byte);
+        }
+        // 2) remove entries one by one, shrinking it back
+        for byte in 0..0xff {
+            assert_eq!(trie.remove(&[byte, byte]).unwrap(), Some(byte));
+            assert_eq!(trie.get(&[byte, byte]).unwrap(), None);
+            for remaining in byte + 1..0xff {
+                trie.check_existence(&[remaining, remaining], remaining);
+            }
+        }
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn it_compresses_single_child_paths() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"http://example.com/a", 1).unwrap();
+        trie.insert(b"http://example.com/b", 2).unwrap();
+
+        match trie.root {
+            Some(Child::Node(ref root)) => {
+                assert_eq!(root.prefix, b"http://example.com/");
+                assert_eq!(root.inner.nb_children(), 2);
+            }
+            _ => panic!("expected the root to be an inner node"),
+        }
+    }
+
+    #[test]
+    fn it_splits_mismatching_prefixes() {
+        let mut trie = Trie::for_utf8();
+        trie.check_insertion(b"abcdef", 1);
+        trie.check_insertion(b"abcxyz", 2);
+        trie.check_insertion(b"abc", 3);
+        trie.check_insertion(b"ab", 4);
+        trie.check_insertion(b"b", 5);
+
+        trie.check_existence(b"abcdef", 1);
+        trie.check_existence(b"abcxyz", 2);
+        trie.check_existence(b"abc", 3);
+        trie.check_existence(b"ab", 4);
+        assert_eq!(trie.get(b"abcd").unwrap(), None);
+        assert_eq!(trie.get(b"abcx").unwrap(), None);
+        assert_eq!(trie.get(b"a").unwrap(), None);
+        assert_eq!(trie.get(b"abcdefg").unwrap(), None);
+    }
+
+    #[test]
+    fn it_merges_nodes_back_after_removal() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"abcdef", 1).unwrap();
+        trie.insert(b"abcxyz", 2).unwrap();
+        trie.insert(b"b", 3).unwrap();
+
+        trie.remove(b"b").unwrap();
+        trie.remove(b"abcxyz").unwrap();
+
+        match trie.root {
+            Some(Child::Leaf(ref key, value)) => {
+                assert_eq!(key, b"abcdef");
+                assert_eq!(value, 1);
+            }
+            _ => panic!("expected the root to be a leaf"),
+        }
+        trie.check_existence(b"abcdef", 1);
+    }
+
+    #[test]
+    fn it_stores_unique_suffixes_in_leaves() {
+        let mut trie = Trie::for_utf8();
+        trie.check_insertion(b"user:1234:name", 1);
+        match trie.root {
+            Some(Child::Leaf(ref key, _)) => assert_eq!(key, b"user:1234:name"),
+            _                             => panic!("expected the root to be a leaf"),
+        }
+
+        trie.check_insertion(b"user:1234:mail", 2);
+        trie.check_insertion(b"user:1234", 3);
+        match trie.root {
+            Some(Child::Node(ref root)) => {
+                assert_eq!(root.prefix, b"user:1234");
+                match root.find_child(b':') {
+                    Some(Child::Node(ref node)) => assert!(node.prefix.is_empty()),
+                    _                           => panic!("expected an inner node"),
+                }
+                match root.find_child(0xff) {
+                    Some(Child::Leaf(ref key, _)) => assert!(key.is_empty()),
+                    _                             => panic!("expected a leaf"),
+                }
+            }
+            _ => panic!("expected the root to be an inner node"),
+        }
+
+        trie.check_existence(b"user:1234:name", 1);
+        trie.check_existence(b"user:1234:mail", 2);
+        assert_eq!(trie.get(b"user:1234:").unwrap(), None);
+        assert_eq!(trie.get(b"user:1234:names").unwrap(), None);
+        assert_eq!(trie.get(b"user:1234:nam").unwrap(), None);
+    }
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn next_key(&mut self) -> Vec<u8> {
+            // a wide first byte to grow the root, then a few shared prefixes
+            let len = (self.next() % 6) as usize;
+            (0..len).map(|i| match i {
+                0 => (self.next() % 64) as u8 + 1,
+                _ => b"abcd"[(self.next() % 4) as usize],
+            }).collect()
+        }
+    }
+
+    #[test]
+    fn it_behaves_like_a_btreemap() {
+        use std::collections::BTreeMap;
+
+        let mut rng = XorShift(0x2545f4914f6cdd1d);
+        let mut trie = Trie::for_utf8();
+        let mut map = BTreeMap::new();
+        for i in 0..10_000 {
+            let key = rng.next_key();
+            if rng.next() % 3 == 0 {
+                assert_eq!(trie.remove(&key).unwrap(), map.remove(&key));
+            } else {
+                assert_eq!(trie.insert(&key, i).unwrap(), map.insert(key, i));
+            }
+            assert_eq!(trie.len(), map.len());
+        }
+
+        for _ in 0..1_000 {
+            let key = rng.next_key();
+            assert_eq!(trie.get(&key).unwrap(), map.get(&key));
+        }
+        for key in map.keys().cloned().collect::<Vec<_>>() {
+            assert_eq!(trie.remove(&key).unwrap(), map.remove(&key));
+        }
+        assert!(trie.is_empty());
+    }
+
+    fn random_trie<'a>(seed: u64, term: u8) -> (Trie<'a, usize>, std::collections::BTreeMap<Vec<u8>, usize>) {
+        let mut rng = XorShift(seed);
+        let mut trie = Trie::with_terminator(term);
+        let mut map = std::collections::BTreeMap::new();
+        for i in 0..2_000 {
+            let key = rng.next_key();
+            if rng.next() % 4 == 0 {
+                trie.remove(&key).unwrap();
+                map.remove(&key);
+            } else {
+                trie.insert(&key, i).unwrap();
+                map.insert(key, i);
+            }
+        }
+        (trie, map)
+    }
+
+    #[test]
+    fn it_iterates_in_key_order() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x9e3779b97f4a7c15, term);
+            assert!(trie.iter().eq(map.iter().map(|(k, v)| (k.clone(), v))));
+            assert!(trie.keys().eq(map.keys().cloned()));
+            assert!(trie.values().eq(map.values()));
+        }
+    }
+
+    #[test]
+    fn it_iterates_over_prefixes_first() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"ab", 2).unwrap();
+        trie.insert(b"b", 3).unwrap();
+        trie.insert(b"a", 1).unwrap();
+        trie.insert(b"", 0).unwrap();
+
+        let entries: Vec<_> = trie.iter().collect();
+        assert_eq!(entries, vec![
+            (b"".to_vec(), &0),
+            (b"a".to_vec(), &1),
+            (b"ab".to_vec(), &2),
+            (b"b".to_vec(), &3),
+        ]);
+    }
+
+    #[test]
+    fn it_iterates_over_nothing_when_empty() {
+        let trie = Trie::<()>::for_utf8();
+        assert_eq!(trie.iter().next(), None);
+    }
+
+    #[test]
+    fn it_updates_values_in_place() {
+        for &term in &[0x00, 0xff] {
+            let (mut trie, mut map) = random_trie(0xd1b54a32d192ed03, term);
+            for (key, value) in trie.iter_mut() {
+                *value += key.len();
+            }
+            for value in trie.values_mut() {
+                *value *= 2;
+            }
+            for (key, value) in map.iter_mut() {
+                *value = (*value + key.len()) * 2;
+            }
+            assert!(trie.iter().eq(map.iter().map(|(k, v)| (k.clone(), v))));
+        }
+    }
+
+    #[test]
+    fn it_moves_values_out_in_key_order() {
+        struct NotClone(usize);
+
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x94d049bb133111eb, term);
+            let mut moved = Trie::with_terminator(term);
+            for (key, value) in trie.iter() {
+                moved.insert(&key, NotClone(*value)).unwrap();
+            }
+
+            let entries: Vec<_> = moved.into_iter()
+                .map(|(key, value)| (key, value.0))
+                .collect();
+            assert_eq!(entries, map.into_iter().collect::<Vec<_>>());
+        }
+    }
+
+    fn random_bound(rng: &mut XorShift) -> Bound<Vec<u8>> {
+        match rng.next() % 3 {
+            0 => Bound::Included(rng.next_key()),
+            1 => Bound::Excluded(rng.next_key()),
+            _ => Bound::Unbounded,
+        }
+    }
+
+    #[test]
+    fn it_iterates_over_ranges() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0xbf58476d1ce4e5b9, term);
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            for _ in 0..500 {
+                let bounds = (random_bound(&mut rng), random_bound(&mut rng));
+                let empty = match bounds {
+                    (Bound::Included(ref start), Bound::Included(ref end)) => start > end,
+                    (Bound::Included(ref start), Bound::Excluded(ref end)) |
+                    (Bound::Excluded(ref start), Bound::Included(ref end)) |
+                    (Bound::Excluded(ref start), Bound::Excluded(ref end)) => start >= end,
+                    _                                                      => false,
+                };
+                let expected: Vec<_> = if empty {
+                    vec![]
+                } else {
+                    map.range::<Vec<u8>, _>(bounds.clone()).map(|(k, v)| (k.clone(), v)).collect()
+                };
+
+                let range = trie.range(bounds.clone()).unwrap();
+                assert_eq!(range.collect::<Vec<_>>(), expected);
+
+                let range = trie.range(bounds.clone()).unwrap();
+                assert_eq!(range.rev().collect::<Vec<_>>(), expected.iter().cloned().rev().collect::<Vec<_>>());
+
+                // alternate between both ends
+                let mut range = trie.range(bounds).unwrap();
+                let (mut front, mut back) = (0, expected.len());
+                while front < back {
+                    if rng.next() % 2 == 0 {
+                        assert_eq!(range.next().as_ref(), Some(&expected[front]));
+                        front += 1;
+                    } else {
+                        back -= 1;
+                        assert_eq!(range.next_back().as_ref(), Some(&expected[back]));
+                    }
+                }
+                assert_eq!(range.next(), None);
+                assert_eq!(range.next_back(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn it_accepts_borrowed_ranges() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"user:099", 0).unwrap();
+        trie.insert(b"user:100", 1).unwrap();
+        trie.insert(b"user:150", 2).unwrap();
+        trie.insert(b"user:200", 3).unwrap();
+
+        let values: Vec<_> = trie.range(b"user:100".as_ref()..b"user:200".as_ref()).unwrap()
+            .map(|(_, value)| *value)
+            .collect();
+        assert_eq!(values, vec![1, 2]);
+
+        let values: Vec<_> = trie.range::<[u8], _>((Bound::Excluded(&b"user:100"[..]), Bound::Unbounded)).unwrap()
+            .rev()
+            .map(|(_, value)| *value)
+            .collect();
+        assert_eq!(values, vec![3, 2]);
+
+        assert!(trie.range(b"user:\xff".as_ref()..).is_err());
+    }
+
+    #[test]
+    fn it_iterates_over_keys_starting_with_a_prefix() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x853c49e6748fea9b, term);
+            let mut rng = XorShift(0xda942042e4dd58b5);
+            for _ in 0..500 {
+                let prefix = rng.next_key();
+                let expected: Vec<_> = map.iter()
+                    .filter(|(key, _)| key.starts_with(&prefix))
+                    .map(|(key, value)| (key.clone(), value))
+                    .collect();
+                assert_eq!(trie.prefix_iter(&prefix).unwrap().collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn it_iterates_over_prefixes_ending_inside_a_node() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"tenant:acme/users/1", 1).unwrap();
+        trie.insert(b"tenant:acme/users/2", 2).unwrap();
+        trie.insert(b"tenant:globex/users/1", 3).unwrap();
+
+        let keys: Vec<_> = trie.prefix_iter(b"tenant:ac").unwrap().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"tenant:acme/users/1".to_vec(), b"tenant:acme/users/2".to_vec()]);
+
+        let keys: Vec<_> = trie.prefix_iter(b"tenant:globex/u").unwrap().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"tenant:globex/users/1".to_vec()]);
+
+        assert_eq!(trie.prefix_iter(b"tenant:acme/users/3").unwrap().next(), None);
+        assert_eq!(trie.prefix_iter(b"").unwrap().count(), 3);
+        assert!(trie.prefix_iter(b"tenant:\xff").is_err());
+    }
+
+    #[test]
+    fn it_finds_the_longest_stored_prefix() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"/", 0).unwrap();
+        trie.insert(b"/api", 1).unwrap();
+        trie.insert(b"/api/v1/users", 2).unwrap();
+        trie.insert(b"/static", 3).unwrap();
+
+        assert_eq!(trie.longest_prefix(b"/api/v1/users/42").unwrap(), Some((13, &2)));
+        assert_eq!(trie.longest_prefix(b"/api/v1/user").unwrap(), Some((4, &1)));
+        assert_eq!(trie.longest_prefix(b"/api").unwrap(), Some((4, &1)));
+        assert_eq!(trie.longest_prefix(b"/index.html").unwrap(), Some((1, &0)));
+        assert_eq!(trie.longest_prefix(b"api").unwrap(), None);
+        assert!(trie.longest_prefix(b"/\xff").is_err());
+    }
+
+    #[test]
+    fn it_finds_the_same_longest_prefixes_as_a_scan() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x5851f42d4c957f2d, term);
+            let mut rng = XorShift(0x14057b7ef767814f);
+            for _ in 0..500 {
+                let key = rng.next_key();
+                let expected = (0..=key.len()).rev()
+                    .find_map(|len| map.get(&key[..len]).map(|value| (len, value)));
+                assert_eq!(trie.longest_prefix(&key).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn it_finds_all_stored_prefixes_shortest_first() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"", 0).unwrap();
+        trie.insert(b"in", 1).unwrap();
+        trie.insert(b"inter", 2).unwrap();
+        trie.insert(b"internal", 3).unwrap();
+        trie.insert(b"international", 4).unwrap();
+
+        let prefixes: Vec<_> = trie.prefixes_of(b"internationalization").unwrap().collect();
+        assert_eq!(prefixes, vec![(0, &0), (2, &1), (5, &2), (13, &4)]);
+
+        let prefixes: Vec<_> = trie.prefixes_of(b"inte").unwrap().collect();
+        assert_eq!(prefixes, vec![(0, &0), (2, &1)]);
+
+        assert!(trie.prefixes_of(b"in\xff").is_err());
+    }
+
+    #[test]
+    fn it_finds_the_same_prefixes_as_a_scan() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x7a6e2b3c1d0f9e8a, term);
+            let mut rng = XorShift(0x3c6ef372fe94f82b);
+            for _ in 0..500 {
+                let input = rng.next_key();
+                let expected: Vec<_> = (0..=input.len())
+                    .filter_map(|len| map.get(&input[..len]).map(|value| (len, value)))
+                    .collect();
+                assert_eq!(trie.prefixes_of(&input).unwrap().collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn it_counts_with_entries() {
+        let mut trie = Trie::for_utf8();
+        for word in "the cat and the dog and the bird".split(' ') {
+            *trie.entry(word.as_bytes()).unwrap().or_insert(0) += 1;
+        }
+
+        assert_eq!(trie.get(b"the").unwrap(), Some(&3));
+        assert_eq!(trie.get(b"and").unwrap(), Some(&2));
+        assert_eq!(trie.get(b"bird").unwrap(), Some(&1));
+        assert!(trie.entry(b"\xff").is_err());
+
+        trie.entry(b"cat").unwrap().and_modify(|count| *count *= 10).or_insert(0);
+        trie.entry(b"cow").unwrap().and_modify(|count| *count *= 10).or_insert(7);
+        assert_eq!(trie.get(b"cat").unwrap(), Some(&10));
+        assert_eq!(trie.get(b"cow").unwrap(), Some(&7));
+
+        match trie.entry(b"dog").unwrap() {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), b"dog");
+                assert_eq!(entry.insert(5), 1);
+            }
+            Entry::Vacant(_) => panic!("dog should be occupied"),
+        }
+        match trie.entry(b"do").unwrap() {
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(4), 4),
+            Entry::Occupied(_) => panic!("do should be vacant"),
+        }
+        assert_eq!(trie.get(b"dog").unwrap(), Some(&5));
+        assert_eq!(trie.get(b"do").unwrap(), Some(&4));
+
+        match trie.entry(b"do").unwrap() {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 4),
+            Entry::Vacant(_) => panic!("do should be occupied"),
+        }
+        assert_eq!(trie.get(b"do").unwrap(), None);
+        assert_eq!(trie.get(b"dog").unwrap(), Some(&5));
+        assert_eq!(trie.len(), 6);
+    }
+
+    #[test]
+    fn it_upserts_like_a_btreemap() {
+        for &term in &[0x00, 0xff] {
+            let mut trie = Trie::with_terminator(term);
+            let mut map = std::collections::BTreeMap::new();
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            for i in 0..2000 {
+                let key = rng.next_key();
+                if i % 3 == 0 {
+                    let removed = match trie.entry(&key).unwrap() {
+                        Entry::Occupied(entry) => Some(entry.remove_entry()),
+                        Entry::Vacant(_)       => None,
+                    };
+                    assert_eq!(removed, map.remove_entry(&key));
+                } else {
+                    *trie.entry(&key).unwrap().or_insert_with(|| i) += 1;
+                    *map.entry(key).or_insert_with(|| i) += 1;
+                }
+            }
+            assert_eq!(trie.len(), map.len());
+            #[cfg(feature = "counts")]
+            if let Some(root) = trie.root.as_ref() {
+                assert_eq!(check_counts(root), map.len());
+            }
+            assert!(trie.iter().map(|(key, value)| (key, *value)).eq(map.into_iter()));
+        }
+    }
+
+    #[test]
+    fn it_updates_values_through_get_mut() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"abc", 1).unwrap();
+        trie.insert(b"abd", 2).unwrap();
+        trie.insert(b"ab", 3).unwrap();
+
+        *trie.get_mut(b"abd").unwrap().unwrap() += 10;
+        *trie.get_mut(b"ab").unwrap().unwrap() += 20;
+        assert_eq!(trie.get_mut(b"a").unwrap(), None);
+        assert_eq!(trie.get_mut(b"abcd").unwrap(), None);
+        assert!(trie.get_mut(b"a\xff").is_err());
+
+        assert_eq!(trie.get(b"abc").unwrap(), Some(&1));
+        assert_eq!(trie.get(b"abd").unwrap(), Some(&12));
+        assert_eq!(trie.get(b"ab").unwrap(), Some(&23));
+    }
+
+    #[test]
+    fn it_collects_and_extends() {
+        let mut trie: Trie<_> = vec![("b", 2), ("a", 1)].into_iter().collect();
+        trie.extend(vec![(b"c".to_vec(), 3), (b"a".to_vec(), 4)]);
+        assert_eq!(trie.len(), 3);
+        assert_eq!(trie.get(b"a").unwrap(), Some(&4));
+
+        let mut trie = Trie::for_ascii();
+        assert!(trie.try_extend(vec![("x", 1), ("y\0", 2), ("z", 3)]).is_err());
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec![b"x".to_vec()]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_panics_when_collecting_keys_containing_the_terminator() {
+        let _: Trie<_> = vec![(b"\xff".to_vec(), 1)].into_iter().collect();
+    }
+
+    #[test]
+    fn it_converts_to_and_from_std_maps() {
+        use std::collections::{BTreeMap, HashMap};
+        use std::convert::TryFrom;
+
+        let (trie, map) = random_trie(0x9e3779b97f4a7c15, 0xff);
+        let converted = BTreeMap::from(trie);
+        assert_eq!(converted, map);
+
+        let trie = Trie::try_from(converted).unwrap();
+        let converted = HashMap::from(trie);
+        assert_eq!(converted.len(), map.len());
+
+        let trie = Trie::try_from(converted).unwrap();
+        assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+
+        let mut strings = BTreeMap::new();
+        strings.insert("héllo".to_string(), 1);
+        strings.insert("hé".to_string(), 2);
+        let trie = Trie::from(strings.clone());
+        assert_eq!(trie.get("hé".as_bytes()).unwrap(), Some(&2));
+        let trie = Trie::from(strings.into_iter().collect::<HashMap<_, _>>());
+        assert_eq!(trie.get("héllo".as_bytes()).unwrap(), Some(&1));
+
+        let mut bytes = HashMap::new();
+        bytes.insert(b"\xff".to_vec(), 1);
+        assert!(Trie::try_from(bytes).is_err());
+    }
+
+    #[test]
+    fn it_clones_deeply() {
+        let (trie, map) = random_trie(0x5851f42d4c957f2d, 0xff);
+        let mut clone = trie.clone();
+        for value in clone.values_mut() {
+            *value += 1;
+        }
+        clone.insert(b"new", 0).unwrap();
+
+        assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+        assert_eq!(clone.len(), trie.len() + 1);
+        assert!(trie.values().zip(clone.values().filter(|&&value| value != 0)).all(|(a, b)| a + 1 == *b));
+    }
+
+    #[test]
+    fn it_formats_like_a_map() {
         let mut trie = Trie::for_utf8();
-        // 1) insert
-        trie.check_insertion(b"a", 1);
-        trie.check_insertion(b"c", 2);
-        trie.check_insertion(b"d", 3);
-        trie.check_insertion(b"e", 4);
-        trie.check_insertion(b"f", 5);
-        trie.check_insertion(b"g", 6);
-        trie.check_insertion(b"h", 7);
-        trie.check_insertion(b"i", 8);
-        trie.check_insertion(b"j", 9);
-        trie.check_insertion(b"k", 10);
-        trie.check_insertion(b"l", 11);
-        trie.check_insertion(b"m", 12);
-        trie.check_insertion(b"n", 13);
-        trie.check_insertion(b"o", 14);
-        trie.check_insertion(b"p", 15);
-        trie.check_insertion(b"q", 16);
-        trie.check_insertion(b"r", 17);
-        // 2) verify
-        trie.check_existence(b"a", 1);
-        trie.check_existence(b"c", 2);
-        trie.check_existence(b"d", 3);
-        trie.check_existence(b"e", 4);
-        trie.check_existence(b"f", 5);
-        trie.check_existence(b"g", 6);
-        trie.check_existence(b"h", 7);
-        trie.check_existence(b"i", 8);
-        trie.check_existence(b"j", 9);
-        trie.check_existence(b"k", 10);
-        trie.check_existence(b"l", 11);
-        trie.check_existence(b"m", 12);
-        trie.check_existence(b"n", 13);
-        trie.check_existence(b"o", 14);
-        trie.check_existence(b"p", 15);
-        trie.check_existence(b"q", 16);
-        trie.check_existence(b"r", 17);
+        trie.insert(b"b", 2).unwrap();
+        trie.insert(b"a", 1).unwrap();
+        assert_eq!(format!("{:?}", trie), "{[97]: 1, [98]: 2}");
+    }
+
+    #[test]
+    fn it_compares_and_hashes_by_entries() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash(trie: &Trie<usize>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            trie.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        // Growing past 16 children then shrinking back keeps the larger node around for a
+        // while, so both tries end up with different layouts for the same entries.
+        let mut shrunk = Trie::for_utf8();
+        let mut grown = Trie::for_utf8();
+        for i in 0..20 {
+            shrunk.insert(&[b'a' + i], i as usize).unwrap();
+        }
+        for i in 15..20 {
+            shrunk.remove(&[b'a' + i]).unwrap();
+        }
+        for i in (0..15).rev() {
+            grown.insert(&[b'a' + i], i as usize).unwrap();
+        }
+        assert!(shrunk == grown);
+        assert_eq!(hash(&shrunk), hash(&grown));
+
+        // Tries with different terminators can't hold the same keys, so they never compare equal
+        let mut ascii = Trie::for_ascii();
+        for (key, &value) in shrunk.iter() {
+            ascii.insert(&key, value).unwrap();
+        }
+        assert!(ascii != shrunk);
+        assert_ne!(hash(&ascii), hash(&shrunk));
+
+        grown.insert(b"z", 25).unwrap();
+        assert!(shrunk != grown);
+        grown.remove(b"z").unwrap();
+        grown.insert(b"a", 1).unwrap();
+        assert!(shrunk != grown);
+        assert_ne!(hash(&shrunk), hash(&grown));
+    }
+
+    #[test]
+    fn it_finds_and_pops_the_first_and_last_entries() {
+        let mut trie = Trie::for_utf8();
+        assert_eq!(trie.first_key_value(), None);
+        assert_eq!(trie.pop_last(), None);
+
+        trie.insert(b"ab", 1).unwrap();
+        trie.insert(b"a", 2).unwrap();
+        trie.insert(b"b", 3).unwrap();
+        trie.insert(b"ba", 4).unwrap();
+        assert_eq!(trie.first_key_value(), Some((b"a".to_vec(), &2)));
+        assert_eq!(trie.last_key_value(), Some((b"ba".to_vec(), &4)));
+
+        assert_eq!(trie.pop_first(), Some((b"a".to_vec(), 2)));
+        assert_eq!(trie.pop_last(), Some((b"ba".to_vec(), 4)));
+        assert_eq!(trie.pop_last(), Some((b"b".to_vec(), 3)));
+        assert_eq!(trie.pop_first(), Some((b"ab".to_vec(), 1)));
+        assert_eq!(trie.pop_first(), None);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn it_pops_in_the_same_order_as_a_btreemap() {
+        for &term in &[0x00, 0xff] {
+            let (mut trie, mut map) = random_trie(0x14057b7ef767814f, term);
+            let mut rng = XorShift(0x5851f42d4c957f2d);
+            while !map.is_empty() {
+                if rng.next() % 2 == 0 {
+                    assert_eq!(trie.first_key_value(), map.iter().next().map(|(k, v)| (k.clone(), v)));
+                    assert_eq!(trie.pop_first(), map.pop_first());
+                } else {
+                    assert_eq!(trie.last_key_value(), map.iter().next_back().map(|(k, v)| (k.clone(), v)));
+                    assert_eq!(trie.pop_last(), map.pop_last());
+                }
+                assert_eq!(trie.len(), map.len());
+            }
+            assert!(trie.is_empty());
+        }
+    }
+
+    #[test]
+    fn it_finds_the_nearest_entries() {
+        let mut trie = Trie::for_utf8();
+        for &key in &[&b"10:00"[..], b"10:30", b"11", b"12:15"] {
+            trie.insert(key, key.len()).unwrap();
+        }
+
+        assert_eq!(trie.floor(b"10:45").unwrap(), Some((b"10:30".to_vec(), &5)));
+        assert_eq!(trie.floor(b"10:30").unwrap(), Some((b"10:30".to_vec(), &5)));
+        assert_eq!(trie.lower(b"10:30").unwrap(), Some((b"10:00".to_vec(), &5)));
+        assert_eq!(trie.floor(b"09").unwrap(), None);
+        assert_eq!(trie.floor(b"11:59").unwrap(), Some((b"11".to_vec(), &2)));
+
+        assert_eq!(trie.ceiling(b"10:45").unwrap(), Some((b"11".to_vec(), &2)));
+        assert_eq!(trie.ceiling(b"11").unwrap(), Some((b"11".to_vec(), &2)));
+        assert_eq!(trie.higher(b"11").unwrap(), Some((b"12:15".to_vec(), &5)));
+        assert_eq!(trie.higher(b"12:15").unwrap(), None);
+        assert_eq!(trie.ceiling(b"").unwrap(), Some((b"10:00".to_vec(), &5)));
+
+        assert!(trie.floor(b"\xff").is_err());
+    }
+
+    #[test]
+    fn it_finds_the_same_nearest_entries_as_a_btreemap() {
+        use std::ops::Bound::*;
+
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x2545f4914f6cdd1d, term);
+            let mut rng = XorShift(0x9e3779b97f4a7c15);
+            for _ in 0..500 {
+                let key = rng.next_key();
+                let entry = |(key, value): (&Vec<u8>, _)| (key.clone(), value);
+                let below = |bound| map.range::<[u8], _>((Unbounded, bound)).next_back().map(entry);
+                let above = |bound| map.range::<[u8], _>((bound, Unbounded)).next().map(entry);
+                assert_eq!(trie.floor(&key).unwrap(), below(Included(&key[..])));
+                assert_eq!(trie.lower(&key).unwrap(), below(Excluded(&key[..])));
+                assert_eq!(trie.ceiling(&key).unwrap(), above(Included(&key[..])));
+                assert_eq!(trie.higher(&key).unwrap(), above(Excluded(&key[..])));
+            }
+        }
+    }
+
+    #[test]
+    fn it_moves_a_cursor_both_ways() {
+        let mut trie = Trie::for_utf8();
+        for (i, key) in ["page:1", "page:2", "page:3", "post:1"].iter().enumerate() {
+            trie.insert(key.as_bytes(), i).unwrap();
+        }
+
+        let mut cursor = trie.cursor();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.seek(b"page:2").unwrap(), Some((&b"page:2"[..], &1)));
+        assert_eq!(cursor.move_next(), Some((&b"page:3"[..], &2)));
+        assert_eq!(cursor.move_next(), Some((&b"post:1"[..], &3)));
+        assert_eq!(cursor.move_next(), None);
+        assert_eq!(cursor.move_next(), Some((&b"page:1"[..], &0)));
+        assert_eq!(cursor.move_prev(), None);
+        assert_eq!(cursor.move_prev(), Some((&b"post:1"[..], &3)));
+        assert_eq!(cursor.key(), Some(&b"post:1"[..]));
+
+        assert_eq!(cursor.seek(b"pa").unwrap(), Some((&b"page:1"[..], &0)));
+        assert_eq!(cursor.seek(b"q").unwrap(), None);
+        assert!(cursor.seek(b"\xff").is_err());
+        assert_eq!(cursor.seek_last(), Some((&b"post:1"[..], &3)));
+    }
+
+    #[test]
+    fn it_pages_through_entries_with_a_cursor() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x3c6ef372fe94f82b, term);
+            let mut cursor = trie.cursor();
+            let mut entries = Vec::new();
+            let mut entry = cursor.seek_first();
+            while let Some((key, value)) = entry {
+                entries.push((key.to_vec(), value));
+                entry = cursor.move_next();
+            }
+            assert!(entries.into_iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+
+            let mut backwards = Vec::new();
+            while let Some((key, _)) = cursor.move_prev() {
+                backwards.push(key.to_vec());
+            }
+            assert!(backwards.into_iter().eq(map.keys().rev().cloned()));
+        }
+    }
+
+    #[test]
+    fn it_modifies_entries_through_a_cursor() {
+        let (mut trie, map) = random_trie(0x14057b7ef767814f, 0xff);
+        let start = map.keys().nth(map.len() / 2).unwrap().clone();
+
+        let mut cursor = trie.cursor_mut();
+        cursor.seek(&start).unwrap();
+        *cursor.value_mut().unwrap() = 0;
+        while let Some((_, value)) = cursor.move_next() {
+            *value = 0;
+        }
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.move_prev().map(|(key, _)| key.to_vec()), map.keys().next_back().cloned());
+
+        let mut entry = cursor.seek_last();
+        while let Some((_, value)) = entry {
+            *value += 1;
+            entry = cursor.move_prev();
+        }
+        assert_eq!(cursor.value(), None);
+
+        for (key, value) in trie.iter() {
+            let expected = if key < start { map[&key] } else { 0 };
+            assert_eq!(*value, expected + 1);
+        }
+    }
+
+    #[test]
+    fn it_retains_matching_entries() {
+        for &term in &[0x00, 0xff] {
+            let (mut trie, mut map) = random_trie(0x7a6e2b3c1d0f9e8a, term);
+            let mut visited = Vec::new();
+            trie.retain(|key, value| {
+                visited.push(key.to_vec());
+                *value += 1;
+                key.len() != 2 && *value % 3 == 0
+            });
+            assert!(visited.iter().eq(map.keys()));
+
+            map.retain(|key, value| {
+                *value += 1;
+                key.len() != 2 && *value % 3 == 0
+            });
+            assert_eq!(trie.len(), map.len());
+            assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+
+            trie.retain(|_, _| false);
+            assert!(trie.is_empty());
+            assert_eq!(trie.first_key_value(), None);
+        }
+    }
+
+    #[test]
+    fn it_removes_and_drains_prefixes() {
+        let mut trie = Trie::for_utf8();
+        for (i, key) in ["acme/a", "acme/b", "acme", "acme2/a", "globex/a"].iter().enumerate() {
+            trie.insert(key.as_bytes(), i).unwrap();
+        }
+
+        assert_eq!(trie.remove_prefix(b"acme/").unwrap(), 2);
+        assert_eq!(trie.remove_prefix(b"acme/").unwrap(), 0);
+        assert_eq!(trie.len(), 3);
+
+        let drained: Vec<_> = trie.drain_prefix(b"ac").unwrap().collect();
+        assert_eq!(drained, vec![(b"acme".to_vec(), 2), (b"acme2/a".to_vec(), 3)]);
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec![b"globex/a".to_vec()]);
+        assert!(trie.drain_prefix(b"\xff").is_err());
+
+        assert_eq!(trie.remove_prefix(b"").unwrap(), 1);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn it_drains_the_same_entries_as_a_scan() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            for round in 0..50 {
+                let (mut trie, mut map) = random_trie(round, term);
+                let prefix = rng.next_key();
+                let expected: Vec<_> = map.keys().filter(|key| key.starts_with(&prefix)).cloned().collect();
+                let drained: Vec<_> = trie.drain_prefix(&prefix).unwrap().collect();
+                assert!(drained.iter().map(|(key, _)| key).eq(expected.iter()));
+                for key in &expected {
+                    map.remove(key);
+                }
+                assert_eq!(trie.len(), map.len());
+                assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+                for (key, value) in &map {
+                    assert_eq!(trie.get(key).unwrap(), Some(value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_splits_off_like_a_btreemap() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x14057b7ef767814f);
+            for round in 0..20 {
+                let (mut trie, mut map) = random_trie(round, term);
+                let key = rng.next_key();
+                let other = trie.split_off(&key).unwrap();
+                let other_map = map.split_off(&key);
+
+                assert_eq!((trie.len(), other.len()), (map.len(), other_map.len()));
+                assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+                assert!(other.iter().eq(other_map.iter().map(|(key, value)| (key.clone(), value))));
+                for (key, value) in &other_map {
+                    assert_eq!(other.get(key).unwrap(), Some(value));
+                    assert_eq!(trie.get(key).unwrap(), None);
+                }
+            }
+        }
+        assert!(Trie::<()>::for_ascii().split_off(b"\0").is_err());
+    }
+
+    #[test]
+    fn it_appends_like_a_btreemap() {
+        for &term in &[0x00, 0xff] {
+            for round in 0..20 {
+                let (mut trie, mut map) = random_trie(round, term);
+                let (mut other, mut other_map) = random_trie(round + 1000, term);
+                trie.append(&mut other).unwrap();
+                map.append(&mut other_map);
+
+                assert!(other.is_empty());
+                assert_eq!(other.first_key_value(), None);
+                assert_eq!(trie.len(), map.len());
+                assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+                for (key, value) in &map {
+                    assert_eq!(trie.get(key).unwrap(), Some(value));
+                }
+            }
+        }
+
+        let mut trie = Trie::for_ascii();
+        let mut other = Trie::for_utf8();
+        other.insert(b"a", 1).unwrap();
+        assert!(matches!(trie.append(&mut other), Err(TerminatorMismatch)));
+        assert_eq!(other.len(), 1);
+    }
+
+    #[cfg(feature = "counts")]
+    fn check_counts<S>(child: &Child<usize, S>) -> usize {
+        match child {
+            Child::Leaf(..)   => 1,
+            Child::Node(node) => {
+                let mut count = 0;
+                let mut next = node.inner.next_child(None);
+                while let Some((key, child)) = next {
+                    count += check_counts(child);
+                    next = node.inner.next_child(Some(key));
+                }
+                assert_eq!(node.count, count);
+                count
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_counts_entries_under_prefixes() {
+        let mut trie = Trie::for_utf8();
+        for key in &["org:1/user:1", "org:1/user:2", "org:12/user:1", "org:2/user:1"] {
+            trie.insert(key.as_bytes(), ()).unwrap();
+        }
+        assert_eq!(trie.count_prefix(b"org:1").unwrap(), 3);
+        assert_eq!(trie.count_prefix(b"org:1/").unwrap(), 2);
+        assert_eq!(trie.count_prefix(b"org:2/user:1").unwrap(), 1);
+        assert_eq!(trie.count_prefix(b"org:3").unwrap(), 0);
+        assert_eq!(trie.count_prefix(b"").unwrap(), 4);
+        assert!(trie.count_prefix(b"\xff").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_keeps_counts_up_to_date() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x5851f42d4c957f2d);
+            let (mut trie, mut map) = random_trie(0x9e3779b97f4a7c15, term);
+            for round in 0..200 {
+                let key = rng.next_key();
+                match round % 8 {
+                    0 => {
+                        *trie.entry(&key).unwrap().or_insert(0) += 1;
+                        *map.entry(key).or_insert(0) += 1;
+                    }
+                    1 => assert_eq!(trie.pop_first(), map.pop_first()),
+                    2 => assert_eq!(trie.pop_last(), map.pop_last()),
+                    3 => {
+                        trie.retain(|key, _| key.len() != 3);
+                        map.retain(|key, _| key.len() != 3);
+                    }
+                    4 => {
+                        trie.remove_prefix(&key[..key.len().min(2)]).unwrap();
+                        map.retain(|k, _| !k.starts_with(&key[..key.len().min(2)]));
+                    }
+                    5 => {
+                        let (mut other, other_map) = random_trie(round, term);
+                        let mut other = other.split_off(&key).unwrap();
+                        trie.append(&mut other).unwrap();
+                        map.extend(other_map.into_iter().filter(|(k, _)| *k >= key));
+                    }
+                    6 => {
+                        let mut other = trie.split_off(&key).unwrap();
+                        let mut other_map = map.split_off(&key);
+                        if let Some(root) = other.root.as_ref() {
+                            assert_eq!(check_counts(root), other_map.len());
+                        }
+                        trie.append(&mut other).unwrap();
+                        map.append(&mut other_map);
+                    }
+                    _ => {
+                        trie.insert(&key, round as usize).unwrap();
+                        map.insert(key, round as usize);
+                    }
+                }
+
+                if let Some(root) = trie.root.as_ref() {
+                    assert_eq!(check_counts(root), map.len());
+                }
+                let prefix = rng.next_key();
+                let expected = map.keys().filter(|key| key.starts_with(&prefix)).count();
+                assert_eq!(trie.count_prefix(&prefix).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_ranks_and_selects_like_a_sorted_list() {
+        for &term in &[0x00, 0xff] {
+            let (trie, map) = random_trie(0x14057b7ef767814f, term);
+            let keys: Vec<_> = map.keys().cloned().collect();
+            for (n, (key, value)) in map.iter().enumerate() {
+                assert_eq!(trie.select(n), Some((key.clone(), value)));
+                assert_eq!(trie.rank(key).unwrap(), n);
+            }
+            assert_eq!(trie.select(map.len()), None);
+
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            for _ in 0..500 {
+                let key = rng.next_key();
+                let expected = keys.partition_point(|k| *k < key);
+                assert_eq!(trie.rank(&key).unwrap(), expected);
+            }
+        }
+        assert!(Trie::<()>::for_ascii().rank(b"\0").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_samples_every_entry() {
+        let mut trie = Trie::for_utf8();
+        assert_eq!(trie.sample(|_| 0), None);
+        for key in &["a", "ab", "abc", "b", "bcd", "bce"] {
+            trie.insert(key.as_bytes(), key.len()).unwrap();
+        }
+
+        let mut rng = XorShift(0x9e3779b97f4a7c15);
+        let mut seen = std::collections::BTreeMap::new();
+        for _ in 0..600 {
+            let (key, value) = trie.sample(|n| rng.next() as usize % n).unwrap();
+            assert_eq!(trie.get(&key).unwrap(), Some(value));
+            *seen.entry(key).or_insert(0) += 1;
+        }
+        assert_eq!(seen.len(), trie.len());
+        assert!(seen.values().all(|&count| count > 50));
+    }
+
+    #[derive(Default)]
+    struct Total(usize);
+
+    impl Summary<usize> for Total {
+        fn from_value(value: &usize) -> Self {
+            Total(*value)
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Total(self.0 + other.0)
+        }
+    }
+
+    /// The values in key order, which only a summary combined in the same order gets right.
+    #[derive(Default, Debug, PartialEq)]
+    struct InOrder(Vec<usize>);
+
+    impl Summary<usize> for InOrder {
+        fn from_value(value: &usize) -> Self {
+            InOrder(vec![*value])
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            InOrder(self.0.iter().chain(&other.0).cloned().collect())
+        }
+    }
+
+    #[test]
+    fn it_aggregates_prefixes_and_ranges() {
+        let mut trie: Trie<usize, Total> = Trie::summarized(0xff);
+        for &(key, size) in &[("/home/a/x", 10), ("/home/a/y", 5), ("/home/b", 7), ("/var/log", 3)] {
+            trie.insert(key.as_bytes(), size).unwrap();
+        }
+        assert_eq!(trie.aggregate_prefix(b"/home/").unwrap().0, 22);
+        assert_eq!(trie.aggregate_prefix(b"/home/a").unwrap().0, 15);
+        assert_eq!(trie.aggregate_prefix(b"/home/a/y").unwrap().0, 5);
+        assert_eq!(trie.aggregate_prefix(b"/tmp").unwrap().0, 0);
+        assert_eq!(trie.aggregate_prefix(b"").unwrap().0, 25);
+        assert_eq!(trie.aggregate_range(b"/home/a/y".as_ref()..b"/var".as_ref()).unwrap().0, 12);
+        assert_eq!(trie.aggregate_range(..=b"/home/b".as_ref()).unwrap().0, 22);
+        assert!(trie.aggregate_prefix(b"\xff").is_err());
+        assert!(trie.aggregate_range(b"\xff".as_ref()..).is_err());
+
+        trie.insert(b"/home/a/x", 1).unwrap();
+        trie.remove(b"/var/log").unwrap();
+        assert_eq!(trie.aggregate_prefix(b"").unwrap().0, 13);
+    }
+
+    /// Check the summary of each node below the child against its entries, returning their values.
+    fn check_summaries(child: &Child<usize, InOrder>, term: u8) -> Vec<usize> {
+        match child {
+            Child::Leaf(_, value) => vec![*value],
+            Child::Node(node)     => {
+                let mut values = Vec::new();
+                let mut next = node.next_child(None, term);
+                while let Some((key, child)) = next {
+                    values.extend(check_summaries(child, term));
+                    next = node.next_child(Some(key), term);
+                }
+                assert_eq!(node.summary.0, values);
+                values
+            }
+        }
+    }
+
+    #[test]
+    fn it_keeps_summaries_up_to_date() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x5851f42d4c957f2d);
+            let mut trie: Trie<usize, InOrder> = Trie::summarized(term);
+            let mut map = std::collections::BTreeMap::new();
+            for round in 0..2_000 {
+                let key = rng.next_key();
+                match round % 16 {
+                    0 => assert_eq!(trie.pop_first(), map.pop_first()),
+                    1 => assert_eq!(trie.pop_last(), map.pop_last()),
+                    2 => {
+                        trie.retain(|key, _| key.len() != 3);
+                        map.retain(|key, _| key.len() != 3);
+                    }
+                    3 => {
+                        trie.remove_prefix(&key[..key.len().min(2)]).unwrap();
+                        map.retain(|k, _| !k.starts_with(&key[..key.len().min(2)]));
+                    }
+                    4 => {
+                        let mut other = trie.split_off(&key).unwrap();
+                        let mut other_map = map.split_off(&key);
+                        let values = other.root.as_ref().map_or_else(Vec::new, |root| check_summaries(root, term));
+                        assert_eq!(values, other_map.values().cloned().collect::<Vec<_>>());
+                        assert_eq!(other.aggregate_prefix(b"").unwrap().0, values);
+                        trie.append(&mut other).unwrap();
+                        map.append(&mut other_map);
+                    }
+                    5..=8 => {
+                        trie.remove(&key).unwrap();
+                        map.remove(&key);
+                    }
+                    _ => {
+                        trie.insert(&key, round).unwrap();
+                        map.insert(key, round);
+                    }
+                }
+
+                let values = trie.root.as_ref().map_or_else(Vec::new, |root| check_summaries(root, term));
+                assert_eq!(values, map.values().cloned().collect::<Vec<_>>());
+
+                let prefix = rng.next_key();
+                let expected: Vec<_> = map.iter().filter(|(key, _)| key.starts_with(&prefix)).map(|(_, &value)| value).collect();
+                assert_eq!(trie.aggregate_prefix(&prefix).unwrap().0, expected);
+
+                let (low, high) = (rng.next_key(), rng.next_key());
+                let bounds = [Bound::Included(&low[..]), Bound::Excluded(&low[..]), Bound::Unbounded];
+                let range = (bounds[round % 3], bounds[round / 3 % 3].map(|_| &high[..]));
+                let expected: Vec<_> = map.iter().filter(|(key, _)| range.contains(&key[..])).map(|(_, &value)| value).collect();
+                assert_eq!(trie.aggregate_range::<[u8], _>(range).unwrap().0, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn it_views_subtries() {
+        let mut trie = Trie::for_utf8();
+        for key in &["tenant:1/a", "tenant:1/b", "tenant:12/a", "tenant:2/a"] {
+            trie.insert(key.as_bytes(), key.len()).unwrap();
+        }
+
+        let tenant = trie.subtrie(b"tenant:1/").unwrap().unwrap();
+        assert_eq!(tenant.get(b"a").unwrap(), Some(&10));
+        assert!(tenant.contains(b"b").unwrap());
+        assert!(!tenant.contains(b"").unwrap());
+        assert!(tenant.get(b"\xff").is_err());
+        assert_eq!(tenant.len(), 2);
+        let keys: Vec<_> = tenant.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec()]);
+
+        let tenants = trie.subtrie(b"ten").unwrap().unwrap();
+        assert_eq!(tenants.get(b"ant:12/a").unwrap(), Some(&11));
+        assert_eq!(tenants.len(), 4);
+        assert_eq!(tenants.iter().next_back(), Some((b"ant:2/a".to_vec(), &10)));
+
+        assert_eq!(trie.subtrie(b"tenant:1/a").unwrap().unwrap().get(b"").unwrap(), Some(&10));
+        assert!(trie.subtrie(b"tenant:3").unwrap().is_none());
+        assert!(trie.subtrie(b"\xff").is_err());
+    }
+
+    #[test]
+    fn it_inserts_through_subtries() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            let (mut trie, mut map) = random_trie(0x9e3779b97f4a7c15, term);
+            for round in 0..500 {
+                let prefix = rng.next_key();
+                let key = rng.next_key();
+                let full = [&prefix[..], &key[..]].concat();
+                match trie.subtrie_mut(&prefix).unwrap() {
+                    Some(mut subtrie) => {
+                        assert_eq!(subtrie.get(&key).unwrap(), map.get(&full));
+                        assert_eq!(subtrie.insert(&key, round).unwrap(), map.insert(full, round));
+                        assert_eq!(subtrie.get(&key).unwrap(), Some(&round));
+                        *subtrie.get_mut(&key).unwrap().unwrap() += 1;
+                        *map.get_mut(&[&prefix[..], &key[..]].concat()).unwrap() += 1;
+
+                        let expected: Vec<_> = map.iter()
+                            .filter(|(k, _)| k.starts_with(&prefix))
+                            .map(|(k, v)| (k[prefix.len()..].to_vec(), v))
+                            .collect();
+                        assert_eq!(subtrie.len(), expected.len());
+                        assert_eq!(subtrie.iter().collect::<Vec<_>>(), expected);
+                    }
+                    None => assert!(map.keys().all(|k| !k.starts_with(&prefix))),
+                }
+                assert_eq!(trie.len(), map.len());
+                #[cfg(feature = "counts")]
+                if let Some(root) = trie.root.as_ref() {
+                    assert_eq!(check_counts(root), map.len());
+                }
+            }
+            assert!(trie.iter().eq(map.iter().map(|(k, v)| (k.clone(), v))));
+        }
+    }
+
+    #[test]
+    fn it_renames_prefixes() {
+        let mut trie = Trie::for_utf8();
+        for (value, key) in ["home/a/x", "home/a/y", "home/b/x", "var/x"].iter().enumerate() {
+            trie.insert(key.as_bytes(), value + 1).unwrap();
+        }
+
+        assert!(trie.rename_prefix(b"home/a/", b"home/c/", OnCollision::Fail).unwrap());
+        assert_eq!(trie.get(b"home/c/y").unwrap(), Some(&2));
+        assert_eq!(trie.get(b"home/a/y").unwrap(), None);
+
+        let before = trie.clone();
+        assert!(matches!(trie.rename_prefix(b"home/c/", b"home/b/", OnCollision::Fail), Err(RenameError::PrefixOccupied)));
+        assert_eq!(trie, before);
+
+        let mut add = |existing: &mut usize, value| *existing += value;
+        assert!(trie.rename_prefix(b"home/c/", b"home/b/", OnCollision::Merge(&mut add)).unwrap());
+        assert_eq!(trie.get(b"home/b/x").unwrap(), Some(&4));
+        assert_eq!(trie.get(b"home/b/y").unwrap(), Some(&2));
+        assert_eq!(trie.len(), 3);
+
+        assert!(trie.rename_prefix(b"var/", b"home/", OnCollision::Overwrite).unwrap());
+        let entries: Vec<_> = trie.iter().collect();
+        assert_eq!(entries, vec![(b"home/x".to_vec(), &4)]);
+
+        assert!(!trie.rename_prefix(b"tmp/", b"home/", OnCollision::Overwrite).unwrap());
+        assert_eq!(trie.len(), 1);
+        assert!(matches!(trie.rename_prefix(b"\xff", b"", OnCollision::Fail), Err(RenameError::KeyContainsTerminator)));
+    }
+
+    #[test]
+    fn it_renames_prefixes_like_a_map() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x5851f42d4c957f2d);
+            let (plain, mut map) = random_trie(0x9e3779b97f4a7c15, term);
+            let mut trie: Trie<usize, InOrder> = Trie::summarized(term);
+            for (key, &value) in plain.iter() {
+                trie.insert(&key, value).unwrap();
+            }
+
+            for round in 0..300 {
+                let old = rng.next_key();
+                let new = rng.next_key();
+                let old = &old[..old.len().min(2)];
+                let mut add = |existing: &mut usize, value| *existing += value;
+                let on_collision = match round % 3 {
+                    0 => OnCollision::Fail,
+                    1 => OnCollision::Overwrite,
+                    _ => OnCollision::Merge(&mut add),
+                };
+
+                let moved: Vec<_> = map.keys().filter(|key| key.starts_with(old)).cloned().collect();
+                let moved: Vec<_> = moved.into_iter()
+                    .map(|key| {
+                        let value = map.remove(&key).unwrap();
+                        ([&new[..], &key[old.len()..]].concat(), value)
+                    })
+                    .collect();
+                let occupied = map.keys().any(|key| key.starts_with(&new));
+
+                let renamed = trie.rename_prefix(old, &new, on_collision);
+                if moved.is_empty() {
+                    assert!(!renamed.unwrap());
+                } else if round % 3 == 0 && occupied {
+                    assert!(matches!(renamed, Err(RenameError::PrefixOccupied)));
+                    for (key, value) in moved {
+                        map.insert([old, &key[new.len()..]].concat(), value);
+                    }
+                } else {
+                    assert!(renamed.unwrap());
+                    if round % 3 == 1 {
+                        map.retain(|key, _| !key.starts_with(&new));
+                    }
+                    for (key, value) in moved {
+                        *map.entry(key).or_insert(0) += value;
+                    }
+                }
+
+                assert_eq!(trie.len(), map.len());
+                assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+                assert_eq!(trie.aggregate_prefix(b"").unwrap().0, map.values().cloned().collect::<Vec<_>>());
+                #[cfg(feature = "counts")]
+                if let Some(root) = trie.root.as_ref() {
+                    assert_eq!(check_counts(root), map.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_grafts_subtrees() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"shared", 0).unwrap();
+        let mut worker = Trie::for_utf8();
+        for (value, key) in ["", "a", "ab", "b"].iter().enumerate() {
+            worker.insert(key.as_bytes(), value + 1).unwrap();
+        }
+
+        trie.graft(b"worker:1/", worker.clone()).unwrap();
+        assert_eq!(trie.len(), 5);
+        assert_eq!(trie.get(b"worker:1/").unwrap(), Some(&1));
+        assert_eq!(trie.get(b"worker:1/ab").unwrap(), Some(&3));
+
+        // Grafting onto existing entries replaces their values
+        trie.graft(b"worker:1/a", Trie::from(BTreeMap::from([("b".to_string(), 30)]))).unwrap();
+        assert_eq!(trie.get(b"worker:1/ab").unwrap(), Some(&30));
+        assert_eq!(trie.len(), 5);
+
+        assert!(matches!(trie.graft(b"", Trie::for_ascii()), Err(GraftError::TerminatorMismatch)));
+        assert!(matches!(trie.graft(b"\xff", Trie::for_utf8()), Err(GraftError::KeyContainsTerminator)));
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_grafts_and_prunes_subtrees() {
+        let mut trie = Trie::for_utf8();
+        trie.insert(b"shared", 0).unwrap();
+        let mut worker = Trie::for_utf8();
+        for (value, key) in ["", "a", "ab", "b"].iter().enumerate() {
+            worker.insert(key.as_bytes(), value + 1).unwrap();
+        }
+
+        trie.graft(b"worker:1/", worker.clone()).unwrap();
+        assert_eq!(trie.len(), 5);
+        assert_eq!(trie.get(b"worker:1/").unwrap(), Some(&1));
+        assert_eq!(trie.get(b"worker:1/ab").unwrap(), Some(&3));
+
+        let pruned = trie.prune(b"worker:1/").unwrap();
+        assert_eq!(pruned, worker);
+        assert_eq!(trie.len(), 1);
+        assert_eq!(trie.prune(b"worker").unwrap().len(), 0);
+
+        // Grafting onto existing entries replaces their values
+        trie.graft(b"", pruned).unwrap();
+        trie.graft(b"a", Trie::from(BTreeMap::from([("b".to_string(), 30)]))).unwrap();
+        assert_eq!(trie.get(b"ab").unwrap(), Some(&30));
+        assert_eq!(trie.len(), 5);
+
+        assert!(matches!(trie.graft(b"", Trie::for_ascii()), Err(GraftError::TerminatorMismatch)));
+        assert!(matches!(trie.graft(b"\xff", Trie::for_utf8()), Err(GraftError::KeyContainsTerminator)));
+        assert!(trie.prune(b"\xff").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "counts")]
+    fn it_prunes_and_grafts_back_like_a_map() {
+        for &term in &[0x00, 0xff] {
+            let mut rng = XorShift(0x2545f4914f6cdd1d);
+            let (plain, mut map) = random_trie(0x14057b7ef767814f, term);
+            let mut trie: Trie<usize, InOrder> = Trie::summarized(term);
+            for (key, &value) in plain.iter() {
+                trie.insert(&key, value).unwrap();
+            }
+
+            for _ in 0..300 {
+                let prefix = rng.next_key();
+                let prefix = &prefix[..prefix.len().min(2)];
+                let pruned = trie.prune(prefix).unwrap();
+                let expected: Vec<_> = map.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+                let expected: std::collections::BTreeMap<_, _> = expected.into_iter()
+                    .map(|key| (key[prefix.len()..].to_vec(), map.remove(&key).unwrap()))
+                    .collect();
+                assert!(pruned.iter().eq(expected.iter().map(|(key, value)| (key.clone(), value))));
+                assert_eq!(pruned.len(), expected.len());
+                assert_eq!(pruned.aggregate_prefix(b"").unwrap().0, expected.values().cloned().collect::<Vec<_>>());
+                assert_eq!(trie.len(), map.len());
+
+                let target = rng.next_key();
+                trie.graft(&target, pruned).unwrap();
+                for (key, value) in expected {
+                    map.insert([&target[..], &key[..]].concat(), value);
+                }
+
+                assert_eq!(trie.len(), map.len());
+                assert!(trie.iter().eq(map.iter().map(|(key, value)| (key.clone(), value))));
+                assert_eq!(trie.aggregate_prefix(b"").unwrap().0, map.values().cloned().collect::<Vec<_>>());
+                if let Some(root) = trie.root.as_ref() {
+                    assert_eq!(check_counts(root), map.len());
+                }
+            }
+        }
     }
 }
@@ -29,13 +29,17 @@ use crate::node48::Node48;
 #[cfg(not(feature = "node48"))]
 use crate::node256::Node256;
 
-pub(crate) struct Node16<'a, T> {
+#[cfg(feature = "node4")]
+use crate::node4::Node4;
+
+#[derive(Clone)]
+pub(crate) struct Node16<'a, T, S> {
     child_indices: [u8; 16],
-    children: [Option<Box<Child<'a, T>>>; 16],
+    children: [Option<Box<Child<'a, T, S>>>; 16],
     nb_children: u8,
 }
 
-impl<'a, T> Default for Node16<'a, T> {
+impl<'a, T, S> Default for Node16<'a, T, S> {
     fn default() -> Self {
         Node16 {
             child_indices: [0; 16],
@@ -50,16 +54,28 @@ impl<'a, T> Default for Node16<'a, T> {
     }
 }
 
-impl<'a, T> Node16<'a, T> {
+impl<'a, T, S> Node16<'a, T, S> {
     #[cfg(feature = "node4")]
-    pub fn new(child_indices: [u8; 16], children: [Option<Box<Child<'a, T>>>; 16], nb_children: u8) -> Self {
+    pub fn new(child_indices: [u8; 16], children: [Option<Box<Child<'a, T, S>>>; 16], nb_children: u8) -> Self {
         Node16 { child_indices, children, nb_children }
     }
 
+    pub fn from_children<I>(children: I) -> Self
+        where I: IntoIterator<Item = (u8, Box<Child<'a, T, S>>)>
+    {
+        let mut node = Self::default();
+        for (key, child) in children {
+            node.child_indices[node.nb_children as usize] = key;
+            node.children[node.nb_children as usize] = Some(child);
+            node.nb_children += 1;
+        }
+        node
+    }
+
     #[cfg(feature = "node48")]
-    fn upgrade_to_node48(mut self: Box<Self>) -> Box<Node48<'a, T>> {
+    fn upgrade_to_node48(mut self) -> Box<Node48<'a, T, S>> {
         let mut child_indices = [48; 256];
-        let mut children: [Option<Box<Child<'a, T>>>; 48] = [
+        let mut children: [Option<Box<Child<'a, T, S>>>; 48] = [
             None, None, None, None, None, None,
             None, None, None, None, None, None,
             None, None, None, None, None, None,
@@ -79,8 +95,8 @@ impl<'a, T> Node16<'a, T> {
     }
 
     #[cfg(not(feature = "node48"))]
-    fn upgrade_to_node256(mut self: Box<Self>) -> Box<Node256<'a, T>> {
-        let mut children: [Option<Box<Child<'a, T>>>; 256] = [
+    fn upgrade_to_node256(mut self) -> Box<Node256<'a, T, S>> {
+        let mut children: [Option<Box<Child<'a, T, S>>>; 256] = [
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
@@ -107,15 +123,9 @@ impl<'a, T> Node16<'a, T> {
         Box::new(Node256::new(children))
     }
 
-
     #[cfg(any(feature = "no-simd", all(not(target_arch = "x86"), not(target_arch = "x86_64"))))]
     fn find_child_index(&self, key: u8) -> Option<usize> {
-        for i in 0..self.nb_children {
-            if self.child_indices[i] == key {
-                return Some(i);
-            }
-        }
-        None
+        self.child_indices[..self.nb_children as usize].iter().position(|&k| k == key)
     }
 
     #[cfg(all(not(feature = "no-simd"), any(target_arch = "x86", target_arch = "x86_64")))]
@@ -142,26 +152,10 @@ impl<'a, T> Node16<'a, T> {
     }
 }
 
-impl<'a, T> NodeImpl<'a, T> for Node16<'a, T> {
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) -> Result<(), Child<'a, T>> {
-        if let Some(_) = self.find_child_index(key) {
-            return Ok(());
-        } else {
-            // If we're adding a new entry, there should be less than 16 entries.
-            if self.nb_children < 16 {
-                self.child_indices[self.nb_children as usize] = key;
-                self.children[self.nb_children as usize] = Some(Box::new(child));
-                self.nb_children += 1;
-                return Ok(());
-            }
-        }
-
-        Err(child)
-    }
-
-    fn insert_child(&mut self, key: u8, mut child: Child<'a, T>) -> Result<Option<Child<'a, T>>, Child<'a, T>> {
+impl<'a, T, S> NodeImpl<'a, T, S> for Node16<'a, T, S> {
+    fn insert_child(&mut self, key: u8, mut child: Child<'a, T, S>) -> Result<Option<Child<'a, T, S>>, Child<'a, T, S>> {
         if let Some(index) = self.find_child_index(key) {
-            mem::swap(&mut child, self.children[index as usize].as_mut().unwrap());
+            mem::swap(&mut child, self.children[index].as_mut().unwrap());
             return Ok(Some(child));
         } else {
             // If we're adding a new entry, there should be less than 16 entries.
@@ -176,7 +170,7 @@ impl<'a, T> NodeImpl<'a, T> for Node16<'a, T> {
         Err(child)
     }
 
-    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T> + 'a> {
+    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
         #[cfg(feature = "node48")] {
             self.upgrade_to_node48()
         }
@@ -186,11 +180,109 @@ impl<'a, T> NodeImpl<'a, T> for Node16<'a, T> {
         }
     }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>> {
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>> {
+        if let Some(index) = self.find_child_index(key) {
+            self.children[index].as_deref()
+        } else {
+            None
+        }
+    }
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>> {
         if let Some(index) = self.find_child_index(key) {
-            self.children[index as usize].as_ref().map(|x| &**x)
+            self.children[index].as_deref_mut()
         } else {
             None
         }
     }
+
+    fn next_child(&self, after: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let nb_children = self.nb_children as usize;
+        self.child_indices[..nb_children].iter()
+            .zip(self.children[..nb_children].iter())
+            .filter(|(&k, _)| after.map_or(true, |after| k > after))
+            .min_by_key(|(&k, _)| k)
+            .map(|(&k, child)| (k, child.as_deref().unwrap()))
+    }
+
+    fn prev_child(&self, before: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let nb_children = self.nb_children as usize;
+        self.child_indices[..nb_children].iter()
+            .zip(self.children[..nb_children].iter())
+            .filter(|(&k, _)| before.map_or(true, |before| k < before))
+            .max_by_key(|(&k, _)| k)
+            .map(|(&k, child)| (k, child.as_deref().unwrap()))
+    }
+
+    fn for_each_child(&self, f: &mut dyn FnMut(u8, &Child<'a, T, S>)) {
+        let nb_children = self.nb_children as usize;
+        let mut order = [0; 16];
+        for (index, slot) in order[..nb_children].iter_mut().enumerate() {
+            *slot = index;
+        }
+        order[..nb_children].sort_unstable_by_key(|&index| self.child_indices[index]);
+        for &index in &order[..nb_children] {
+            f(self.child_indices[index], self.children[index].as_deref().unwrap());
+        }
+    }
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>> {
+        let index = self.find_child_index(key)?;
+
+        // Keep the entries contiguous by moving the last one in the freed slot.
+        let last = self.nb_children as usize - 1;
+        self.child_indices.swap(index, last);
+        self.children.swap(index, last);
+        self.nb_children -= 1;
+
+        self.children[last].take().map(|child| *child)
+    }
+
+    fn children_mut(&mut self) -> Vec<(u8, &mut Child<'a, T, S>)> {
+        let nb_children = self.nb_children as usize;
+        self.child_indices[..nb_children].iter()
+            .zip(self.children[..nb_children].iter_mut())
+            .map(|(&key, child)| (key, &mut **child.as_mut().unwrap()))
+            .collect()
+    }
+
+    fn into_children(mut self: Box<Self>) -> Vec<(u8, Box<Child<'a, T, S>>)> {
+        let nb_children = self.nb_children as usize;
+        let child_indices = self.child_indices;
+        self.children[..nb_children].iter_mut()
+            .zip(child_indices.iter())
+            .map(|(child, &key)| (key, child.take().unwrap()))
+            .collect()
+    }
+
+    fn nb_children(&self) -> usize {
+        self.nb_children as usize
+    }
+
+    fn should_downgrade(&self) -> bool {
+        #[cfg(feature = "node4")] {
+            self.nb_children <= 3
+        }
+
+        #[cfg(not(feature = "node4"))] {
+            false
+        }
+    }
+
+    fn downgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
+        #[cfg(feature = "node4")] {
+            Box::new(Node4::from_children(self.into_children()))
+        }
+
+        #[cfg(not(feature = "node4"))] {
+            unreachable!();
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn NodeImpl<'a, T, S> + 'a>
+        where T: Clone,
+              S: Clone,
+    {
+        Box::new(self.clone())
+    }
 }
@@ -5,11 +5,25 @@ use super::{
     NodeImpl,
 };
 
-pub(crate) struct Node256<'a, T> {
-    children: [Option<Box<Child<'a, T>>>; 256]
+use crate::bitmap::Bitmap;
+
+#[cfg(feature = "node48")]
+use crate::node48::Node48;
+
+#[cfg(all(not(feature = "node48"), feature = "node16"))]
+use crate::node16::Node16;
+
+#[cfg(all(not(feature = "node48"), not(feature = "node16"), feature = "node4"))]
+use crate::node4::Node4;
+
+#[derive(Clone)]
+pub(crate) struct Node256<'a, T, S> {
+    children: [Option<Box<Child<'a, T, S>>>; 256],
+    keys: Bitmap,
+    nb_children: u16,
 }
 
-impl<'a, T> Default for Node256<'a, T> {
+impl<'a, T, S> Default for Node256<'a, T, S> {
     fn default() -> Self {
         Node256 {
             children: [
@@ -29,43 +43,139 @@ impl<'a, T> Default for Node256<'a, T> {
                 None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
                 None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
                 None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
-            ]
+            ],
+            keys: Bitmap::default(),
+            nb_children: 0,
         }
     }
 }
 
-impl<'a, T> Node256<'a, T> {
+impl<'a, T, S> Node256<'a, T, S> {
     #[cfg(any(feature = "node4", feature = "node16", feature = "node48"))]
-    pub fn new(children: [Option<Box<Child<'a, T>>>; 256]) -> Self {
-        Node256 { children }
-    }
-}
-
-impl<'a, T> NodeImpl<'a, T> for Node256<'a, T> {
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) -> Result<(), Child<'a, T>> {
-        if let Some(_) = self.children[key as usize].as_mut() {
-            return Ok(());
+    pub fn new(children: [Option<Box<Child<'a, T, S>>>; 256]) -> Self {
+        let mut keys = Bitmap::default();
+        let mut nb_children = 0;
+        for (key, child) in children.iter().enumerate() {
+            if child.is_some() {
+                keys.insert(key as u8);
+                nb_children += 1;
+            }
         }
-
-        self.children[key as usize] = Some(Box::new(child));
-        return Ok(());
+        Node256 { children, keys, nb_children }
     }
+}
 
-    fn insert_child(&mut self, key: u8, mut child: Child<'a, T>) -> Result<Option<Child<'a, T>>, Child<'a, T>> {
+impl<'a, T, S> NodeImpl<'a, T, S> for Node256<'a, T, S> {
+    fn insert_child(&mut self, key: u8, mut child: Child<'a, T, S>) -> Result<Option<Child<'a, T, S>>, Child<'a, T, S>> {
         if let Some(existing_child) = self.children[key as usize].as_mut() {
             mem::swap(&mut child, existing_child);
             return Ok(Some(child));
         }
 
         self.children[key as usize] = Some(Box::new(child));
+        self.keys.insert(key);
+        self.nb_children += 1;
         Ok(None)
     }
 
-    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T> + 'a> {
+    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
         unreachable!();
     }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>> {
-        self.children[key as usize].as_ref().map(|x| &**x)
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>> {
+        self.children[key as usize].as_deref()
+    }
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>> {
+        self.children[key as usize].as_deref_mut()
+    }
+
+    fn next_child(&self, after: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let key = self.keys.next(after)?;
+        Some((key, self.find_child(key).unwrap()))
+    }
+
+    fn prev_child(&self, before: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let key = self.keys.prev(before)?;
+        Some((key, self.find_child(key).unwrap()))
+    }
+
+    fn for_each_child(&self, f: &mut dyn FnMut(u8, &Child<'a, T, S>)) {
+        let mut next = self.keys.next(None);
+        while let Some(key) = next {
+            f(key, self.find_child(key).unwrap());
+            next = self.keys.next(Some(key));
+        }
+    }
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>> {
+        let child = self.children[key as usize].take()?;
+        self.keys.remove(key);
+        self.nb_children -= 1;
+        Some(*child)
+    }
+
+    fn children_mut(&mut self) -> Vec<(u8, &mut Child<'a, T, S>)> {
+        self.children.iter_mut()
+            .enumerate()
+            .filter_map(|(key, child)| child.as_deref_mut().map(|child| (key as u8, child)))
+            .collect()
+    }
+
+    fn into_children(mut self: Box<Self>) -> Vec<(u8, Box<Child<'a, T, S>>)> {
+        let mut children = Vec::with_capacity(self.nb_children as usize);
+        for (key, child) in self.children.iter_mut().enumerate() {
+            if let Some(child) = child.take() {
+                children.push((key as u8, child));
+            }
+        }
+        children
+    }
+
+    fn nb_children(&self) -> usize {
+        self.nb_children as usize
+    }
+
+    fn should_downgrade(&self) -> bool {
+        #[cfg(feature = "node48")] {
+            self.nb_children <= 37
+        }
+
+        #[cfg(all(not(feature = "node48"), feature = "node16"))] {
+            self.nb_children <= 12
+        }
+
+        #[cfg(all(not(feature = "node48"), not(feature = "node16"), feature = "node4"))] {
+            self.nb_children <= 3
+        }
+
+        #[cfg(not(any(feature = "node4", feature = "node16", feature = "node48")))] {
+            false
+        }
+    }
+
+    fn downgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
+        #[cfg(feature = "node48")] {
+            Box::new(Node48::from_children(self.into_children()))
+        }
+
+        #[cfg(all(not(feature = "node48"), feature = "node16"))] {
+            Box::new(Node16::from_children(self.into_children()))
+        }
+
+        #[cfg(all(not(feature = "node48"), not(feature = "node16"), feature = "node4"))] {
+            Box::new(Node4::from_children(self.into_children()))
+        }
+
+        #[cfg(not(any(feature = "node4", feature = "node16", feature = "node48")))] {
+            unreachable!();
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn NodeImpl<'a, T, S> + 'a>
+        where T: Clone,
+              S: Clone,
+    {
+        Box::new(self.clone())
     }
 }
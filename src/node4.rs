@@ -14,19 +14,33 @@ use crate::node48::Node48;
 #[cfg(not(any(feature = "node16", feature = "node48")))]
 use crate::node256::Node256;
 
-pub struct Node4<'a, T> {
-    children: [Option<(u8, Box<Child<'a, T>>)>; 4],
+/// A child along with its key.
+type Slot<'a, T, S> = Option<(u8, Box<Child<'a, T, S>>)>;
+
+#[derive(Clone)]
+pub struct Node4<'a, T, S> {
+    children: [Slot<'a, T, S>; 4],
 }
 
-impl<'a, T> Default for Node4<'a, T> {
+impl<'a, T, S> Default for Node4<'a, T, S> {
     fn default() -> Self {
         Node4 { children: [None, None, None, None] }
     }
 }
 
-impl<'a, T> Node4<'a, T> {
+impl<'a, T, S> Node4<'a, T, S> {
+    pub fn from_children<I>(children: I) -> Self
+        where I: IntoIterator<Item = (u8, Box<Child<'a, T, S>>)>
+    {
+        let mut node = Self::default();
+        for (slot, child) in node.children.iter_mut().zip(children) {
+            *slot = Some(child);
+        }
+        node
+    }
+
     #[cfg(feature = "node16")]
-    fn upgrade_to_node16(mut self: Box<Self>) -> Box<Node16<'a, T>> {
+    fn upgrade_to_node16(mut self) -> Box<Node16<'a, T, S>> {
         let (key_0, child_0) = self.children[0].take().unwrap();
         let (key_1, child_1) = self.children[1].take().unwrap();
         let (key_2, child_2) = self.children[2].take().unwrap();
@@ -42,7 +56,7 @@ impl<'a, T> Node4<'a, T> {
         };
 
         let children = {
-            let mut children: [Option<Box<Child<'a, T>>>; 16] = Default::default();
+            let mut children: [Option<Box<Child<'a, T, S>>>; 16] = Default::default();
             children[0] = Some(child_0);
             children[1] = Some(child_1);
             children[2] = Some(child_2);
@@ -54,14 +68,14 @@ impl<'a, T> Node4<'a, T> {
     }
 
     #[cfg(all(not(feature = "node16"), feature = "node48"))]
-    fn upgrade_to_node48(mut self: Box<Self>) -> Box<Node48<'a, T>> {
+    fn upgrade_to_node48(mut self) -> Box<Node48<'a, T, S>> {
         let (key_0, child_0) = self.children[0].take().unwrap();
         let (key_1, child_1) = self.children[1].take().unwrap();
         let (key_2, child_2) = self.children[2].take().unwrap();
         let (key_3, child_3) = self.children[3].take().unwrap();
 
         let mut child_indices = [48; 256];
-        let mut children: [Option<Box<Child<'a, T>>>; 48] = [
+        let mut children: [Option<Box<Child<'a, T, S>>>; 48] = [
             None, None, None, None, None, None,
             None, None, None, None, None, None,
             None, None, None, None, None, None,
@@ -77,17 +91,17 @@ impl<'a, T> Node4<'a, T> {
         child_indices[key_2 as usize] = 2; children[2] = Some(child_2);
         child_indices[key_3 as usize] = 3; children[3] = Some(child_3);
 
-        Box::new(Node48::new(child_indices, children, 16))
+        Box::new(Node48::new(child_indices, children, 4))
     }
 
     #[cfg(not(any(feature = "node16", feature = "node48")))]
-    fn upgrade_to_node256(mut self: Box<Self>) -> Box<Node256<'a, T>> {
+    fn upgrade_to_node256(mut self) -> Box<Node256<'a, T, S>> {
         let (key_0, child_0) = self.children[0].take().unwrap();
         let (key_1, child_1) = self.children[1].take().unwrap();
         let (key_2, child_2) = self.children[2].take().unwrap();
         let (key_3, child_3) = self.children[3].take().unwrap();
 
-        let mut children: [Option<Box<Child<'a, T>>>; 256] = [
+        let mut children: [Option<Box<Child<'a, T, S>>>; 256] = [
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
@@ -115,15 +129,13 @@ impl<'a, T> Node4<'a, T> {
     }
 }
 
-impl<'a, T> NodeImpl<'a, T> for Node4<'a, T> {
-    fn insert_child(&mut self, key: u8, mut child: Child<'a, T>) -> Result<Option<Child<'a, T>>, Child<'a, T>> {
+impl<'a, T, S> NodeImpl<'a, T, S> for Node4<'a, T, S> {
+    fn insert_child(&mut self, key: u8, mut child: Child<'a, T, S>) -> Result<Option<Child<'a, T, S>>, Child<'a, T, S>> {
         // 1st step: try to replace existing entry
-        for existing_child in self.children.iter_mut() {
-            if let Some((k, existing_child)) = existing_child {
-                if key == *k {
-                    mem::swap(&mut child, existing_child);
-                    return Ok(Some(child));
-                }
+        for (k, existing_child) in self.children.iter_mut().flatten() {
+            if key == *k {
+                mem::swap(&mut child, existing_child);
+                return Ok(Some(child));
             }
         }
 
@@ -138,28 +150,7 @@ impl<'a, T> NodeImpl<'a, T> for Node4<'a, T> {
         Err(child)
     }
 
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) -> Result<(), Child<'a, T>> {
-        // 1st step: try to replace existing entry
-        for existing_child in self.children.iter_mut() {
-            if let Some((k, _)) = existing_child {
-                if key == *k {
-                    return Ok(());
-                }
-            }
-        }
-
-        // 2nd step: try to add a new entry
-        for existing_child in self.children.iter_mut() {
-            if existing_child.is_none() {
-                *existing_child = Some((key, Box::new(child)));
-                return Ok(());
-            }
-        }
-
-        Err(child)
-    }
-
-    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T> + 'a> {
+    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
         #[cfg(feature = "node16")] {
             self.upgrade_to_node16()
         }
@@ -173,14 +164,91 @@ impl<'a, T> NodeImpl<'a, T> for Node4<'a, T> {
         }
     }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>> {
-        for child in self.children.iter() {
-            if let Some((k, child)) = child {
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>> {
+        for (k, child) in self.children.iter().flatten() {
+            if key == *k {
+                return Some(child);
+            }
+        }
+        None
+    }
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>> {
+        for (k, child) in self.children.iter_mut().flatten() {
+            if key == *k {
+                return Some(child);
+            }
+        }
+        None
+    }
+
+    fn next_child(&self, after: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        self.children.iter()
+            .flatten()
+            .filter(|(k, _)| after.map_or(true, |after| *k > after))
+            .min_by_key(|(k, _)| *k)
+            .map(|(k, child)| (*k, &**child))
+    }
+
+    fn prev_child(&self, before: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        self.children.iter()
+            .flatten()
+            .filter(|(k, _)| before.map_or(true, |before| *k < before))
+            .max_by_key(|(k, _)| *k)
+            .map(|(k, child)| (*k, &**child))
+    }
+
+    fn for_each_child(&self, f: &mut dyn FnMut(u8, &Child<'a, T, S>)) {
+        let mut children: [Option<(u8, &Child<'a, T, S>)>; 4] = [None; 4];
+        for (slot, (key, child)) in children.iter_mut().zip(self.children.iter().flatten()) {
+            *slot = Some((*key, child));
+        }
+        children.sort_unstable_by_key(|child| child.map(|(key, _)| key));
+        for &(key, child) in children.iter().flatten() {
+            f(key, child);
+        }
+    }
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>> {
+        for existing_child in self.children.iter_mut() {
+            if let Some((k, _)) = existing_child {
                 if key == *k {
-                    return Some(&child);
+                    return existing_child.take().map(|(_, child)| *child);
                 }
             }
         }
         None
     }
+
+    fn children_mut(&mut self) -> Vec<(u8, &mut Child<'a, T, S>)> {
+        self.children.iter_mut()
+            .flatten()
+            .map(|(key, child)| (*key, &mut **child))
+            .collect()
+    }
+
+    fn into_children(mut self: Box<Self>) -> Vec<(u8, Box<Child<'a, T, S>>)> {
+        self.children.iter_mut()
+            .filter_map(Option::take)
+            .collect()
+    }
+
+    fn nb_children(&self) -> usize {
+        self.children.iter().filter(|child| child.is_some()).count()
+    }
+
+    fn should_downgrade(&self) -> bool {
+        false
+    }
+
+    fn downgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
+        unreachable!();
+    }
+
+    fn clone_box(&self) -> Box<dyn NodeImpl<'a, T, S> + 'a>
+        where T: Clone,
+              S: Clone,
+    {
+        Box::new(self.clone())
+    }
 }
@@ -5,22 +5,51 @@ use super::{
     NodeImpl,
 };
 
+use crate::bitmap::Bitmap;
+
 use crate::node256::Node256;
 
-pub(crate) struct Node48<'a, T> {
+#[cfg(feature = "node16")]
+use crate::node16::Node16;
+
+#[cfg(all(not(feature = "node16"), feature = "node4"))]
+use crate::node4::Node4;
+
+#[derive(Clone)]
+pub(crate) struct Node48<'a, T, S> {
     child_indices: [u8; 256],
-    children: [Option<Box<Child<'a, T>>>; 48],
+    children: [Option<Box<Child<'a, T, S>>>; 48],
+    keys: Bitmap,
     nb_children: u8,
 }
 
-impl<'a, T> Node48<'a, T> {
+impl<'a, T, S> Node48<'a, T, S> {
     #[cfg(any(feature = "node4", feature = "node16"))]
-    pub fn new(child_indices: [u8; 256], children: [Option<Box<Child<'a, T>>>; 48], nb_children: u8) -> Self {
-        Node48 { child_indices, children, nb_children }
+    pub fn new(child_indices: [u8; 256], children: [Option<Box<Child<'a, T, S>>>; 48], nb_children: u8) -> Self {
+        let mut keys = Bitmap::default();
+        for (key, &index) in child_indices.iter().enumerate() {
+            if index < 48 {
+                keys.insert(key as u8);
+            }
+        }
+        Node48 { child_indices, children, keys, nb_children }
+    }
+
+    pub fn from_children<I>(children: I) -> Self
+        where I: IntoIterator<Item = (u8, Box<Child<'a, T, S>>)>
+    {
+        let mut node = Self::default();
+        for (key, child) in children {
+            node.child_indices[key as usize] = node.nb_children;
+            node.children[node.nb_children as usize] = Some(child);
+            node.keys.insert(key);
+            node.nb_children += 1;
+        }
+        node
     }
 
-    fn upgrade_to_node256(mut self: Box<Self>) -> Box<Node256<'a, T>> {
-        let mut children: [Option<Box<Child<'a, T>>>; 256] = [
+    fn upgrade_to_node256(mut self) -> Box<Node256<'a, T, S>> {
+        let mut children: [Option<Box<Child<'a, T, S>>>; 256] = [
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
@@ -39,16 +68,18 @@ impl<'a, T> Node48<'a, T> {
             None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
         ];
 
-        for i in 0..self.child_indices.len() {
-            let key = self.child_indices[i] as usize;
-            mem::swap(&mut children[key], &mut self.children[self.child_indices[key] as usize]);
+        for (key, child) in children.iter_mut().enumerate() {
+            let index = self.child_indices[key] as usize;
+            if index < 48 {
+                mem::swap(child, &mut self.children[index]);
+            }
         }
 
         Box::new(Node256::new(children))
     }
 }
 
-impl<'a, T> Default for Node48<'a, T> {
+impl<'a, T, S> Default for Node48<'a, T, S> {
     fn default() -> Self {
         Node48 {
             child_indices: [48; 256],
@@ -62,37 +93,22 @@ impl<'a, T> Default for Node48<'a, T> {
                 None, None, None, None, None, None,
                 None, None, None, None, None, None,
             ],
+            keys: Bitmap::default(),
             nb_children: 0,
         }
     }
 }
 
-impl<'a, T> NodeImpl<'a, T> for Node48<'a, T> {
-    fn update_child(&mut self, key: u8, child: Child<'a, T>) -> Result<(), Child<'a, T>> {
-        let ref mut index = self.child_indices[key as usize];
+impl<'a, T, S> NodeImpl<'a, T, S> for Node48<'a, T, S> {
+    fn insert_child(&mut self, key: u8, mut child: Child<'a, T, S>) -> Result<Option<Child<'a, T, S>>, Child<'a, T, S>> {
+        let index = &mut self.child_indices[key as usize];
         if *index >= 48 {
             // If we're adding a new entry, there should be less than 48 entries.
-                if self.nb_children < 48 {
-                    *index = self.nb_children;
-                    self.children[*index as usize] = Some(Box::new(child));
-                    self.nb_children += 1;
-                    return Ok(());
-                }
-            } else {
-                return Ok(());
-            }
-
-            Err(child)
-        }
-
-        fn insert_child(&mut self, key: u8, mut child: Child<'a, T>) -> Result<Option<Child<'a, T>>, Child<'a, T>> {
-            let ref mut index = self.child_indices[key as usize];
-            if *index >= 48 {
-                // If we're adding a new entry, there should be less than 48 entries.
-                if self.nb_children < 48 {
-                    *index = self.nb_children;
-                    self.children[*index as usize] = Some(Box::new(child));
-                    self.nb_children += 1;
+            if self.nb_children < 48 {
+                *index = self.nb_children;
+                self.children[*index as usize] = Some(Box::new(child));
+                self.keys.insert(key);
+                self.nb_children += 1;
                 return Ok(None);
             }
         } else {
@@ -103,16 +119,127 @@ impl<'a, T> NodeImpl<'a, T> for Node48<'a, T> {
         Err(child)
     }
 
-    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T> + 'a> {
+    fn upgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
         self.upgrade_to_node256()
     }
 
-    fn find_child(&self, key: u8) -> Option<&Child<'a, T>> {
+    fn find_child(&self, key: u8) -> Option<&Child<'a, T, S>> {
         let index = self.child_indices[key as usize];
         if index < 48 {
-            self.children[index as usize].as_ref().map(|x| &**x)
+            self.children[index as usize].as_deref()
         } else {
             None
         }
     }
+
+    fn find_child_mut(&mut self, key: u8) -> Option<&mut Child<'a, T, S>> {
+        let index = self.child_indices[key as usize];
+        if index < 48 {
+            self.children[index as usize].as_deref_mut()
+        } else {
+            None
+        }
+    }
+
+    fn next_child(&self, after: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let key = self.keys.next(after)?;
+        Some((key, self.find_child(key).unwrap()))
+    }
+
+    fn prev_child(&self, before: Option<u8>) -> Option<(u8, &Child<'a, T, S>)> {
+        let key = self.keys.prev(before)?;
+        Some((key, self.find_child(key).unwrap()))
+    }
+
+    fn for_each_child(&self, f: &mut dyn FnMut(u8, &Child<'a, T, S>)) {
+        let mut next = self.keys.next(None);
+        while let Some(key) = next {
+            f(key, self.find_child(key).unwrap());
+            next = self.keys.next(Some(key));
+        }
+    }
+
+    fn remove_child(&mut self, key: u8) -> Option<Child<'a, T, S>> {
+        let index = self.child_indices[key as usize];
+        if index >= 48 {
+            return None;
+        }
+        self.child_indices[key as usize] = 48;
+        self.keys.remove(key);
+
+        // Keep the entries contiguous by moving the last one in the freed slot.
+        let last = self.nb_children - 1;
+        if index != last {
+            let last_key = self.child_indices.iter().position(|&i| i == last).unwrap();
+            self.child_indices[last_key] = index;
+            self.children.swap(index as usize, last as usize);
+        }
+        self.nb_children -= 1;
+
+        self.children[last as usize].take().map(|child| *child)
+    }
+
+    fn children_mut(&mut self) -> Vec<(u8, &mut Child<'a, T, S>)> {
+        let mut keys = [0; 48];
+        for (key, &index) in self.child_indices.iter().enumerate() {
+            if index < 48 {
+                keys[index as usize] = key as u8;
+            }
+        }
+
+        self.children[..self.nb_children as usize].iter_mut()
+            .zip(keys.iter())
+            .map(|(child, &key)| (key, &mut **child.as_mut().unwrap()))
+            .collect()
+    }
+
+    fn into_children(mut self: Box<Self>) -> Vec<(u8, Box<Child<'a, T, S>>)> {
+        let mut children = Vec::with_capacity(self.nb_children as usize);
+        for key in 0..self.child_indices.len() {
+            let index = self.child_indices[key] as usize;
+            if index < 48 {
+                children.push((key as u8, self.children[index].take().unwrap()));
+            }
+        }
+        children
+    }
+
+    fn nb_children(&self) -> usize {
+        self.nb_children as usize
+    }
+
+    fn should_downgrade(&self) -> bool {
+        #[cfg(feature = "node16")] {
+            self.nb_children <= 12
+        }
+
+        #[cfg(all(not(feature = "node16"), feature = "node4"))] {
+            self.nb_children <= 3
+        }
+
+        #[cfg(not(any(feature = "node4", feature = "node16")))] {
+            false
+        }
+    }
+
+    fn downgrade(self: Box<Self>) -> Box<dyn NodeImpl<'a, T, S> + 'a> {
+        #[cfg(feature = "node16")] {
+            Box::new(Node16::from_children(self.into_children()))
+        }
+
+        #[cfg(all(not(feature = "node16"), feature = "node4"))] {
+            Box::new(Node4::from_children(self.into_children()))
+        }
+
+        #[cfg(not(any(feature = "node4", feature = "node16")))] {
+            unreachable!();
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn NodeImpl<'a, T, S> + 'a>
+        where T: Clone,
+              S: Clone,
+    {
+        Box::new(self.clone())
+    }
 }
@@ -0,0 +1,198 @@
+use super::{
+    iter::Leaves,
+    Child,
+    Iter,
+    KeyContainsTerminator,
+    Summary,
+};
+
+/// A view of the entries whose key starts with a prefix, keyed relative to it.
+pub struct SubTrie<'a, 'b, T, S = ()> {
+    root: &'b Child<'a, T, S>,
+    /// Length of the start of the root's keys consumed by the prefix.
+    skip: usize,
+    term: u8,
+}
+
+impl<'a, 'b, T, S: Summary<T>> SubTrie<'a, 'b, T, S> {
+    pub(crate) fn new(root: &'b Child<'a, T, S>, skip: usize, term: u8) -> Self {
+        SubTrie { root, skip, term }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> Result<bool, KeyContainsTerminator> {
+        self.get(key).map(|value| value.is_some())
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn contains_unchecked(&self, key: &[u8]) -> bool {
+        self.get_impl(key).is_some()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<&'b T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.get_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn get_unchecked(&self, key: &[u8]) -> Option<&'b T> {
+        self.get_impl(key)
+    }
+
+    fn get_impl(&self, key: &[u8]) -> Option<&'b T> {
+        self.root.get_past(self.skip, key, self.term)
+    }
+
+    /// Count the entries in the view, see
+    /// [counting entries](https://github.com/jmcomets/artsy#counting-entries-under-a-prefix) for the cost.
+    pub fn len(&self) -> usize {
+        self.root.count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> Iter<'a, 'b, T, S> {
+        Iter::new(Leaves::new(Some(self.root), Vec::new(), self.term).skip(self.skip))
+    }
+}
+
+/// A mutable view of the entries whose key starts with a prefix, keyed relative to it.
+pub struct SubTrieMut<'a, 'b, T> {
+    root: &'b mut Child<'a, T, ()>,
+    /// Start of the root's keys consumed by the prefix.
+    prefix: Vec<u8>,
+    len: &'b mut usize,
+    /// Entry counts of the nodes above the root.
+    #[cfg(feature = "counts")]
+    counts: Vec<&'b mut usize>,
+    term: u8,
+}
+
+impl<'a, 'b, T> SubTrieMut<'a, 'b, T> {
+    pub(crate) fn new(root: &'b mut Option<Child<'a, T, ()>>, len: &'b mut usize, prefix: &[u8], term: u8) -> Option<Self> {
+        let mut child = root.as_mut()?;
+        let mut depth = 0;
+        #[cfg(feature = "counts")]
+        let mut counts = Vec::new();
+
+        while !child.holds_prefix(&prefix[depth..]) {
+            let node = match child {
+                Child::Node(node) => node,
+                Child::Leaf(..)   => return None,
+            };
+            let rest = prefix[depth..].strip_prefix(&node.prefix[..])?;
+            depth += node.prefix.len() + 1;
+            #[cfg(feature = "counts")]
+            counts.push(&mut node.count);
+            child = node.inner.find_child_mut(rest[0])?;
+        }
+
+        Some(SubTrieMut {
+            root: child,
+            prefix: prefix[depth..].to_vec(),
+            len,
+            #[cfg(feature = "counts")]
+            counts,
+            term,
+        })
+    }
+
+    pub fn contains(&self, key: &[u8]) -> Result<bool, KeyContainsTerminator> {
+        self.get(key).map(|value| value.is_some())
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn contains_unchecked(&self, key: &[u8]) -> bool {
+        self.get_impl(key).is_some()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<&T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.get_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn get_unchecked(&self, key: &[u8]) -> Option<&T> {
+        self.get_impl(key)
+    }
+
+    fn get_impl(&self, key: &[u8]) -> Option<&T> {
+        self.root.get_past(self.prefix.len(), key, self.term)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Result<Option<&mut T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.get_mut_impl(key))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn get_mut_unchecked(&mut self, key: &[u8]) -> Option<&mut T> {
+        self.get_mut_impl(key)
+    }
+
+    fn get_mut_impl(&mut self, key: &[u8]) -> Option<&mut T> {
+        self.root.get_mut_past(self.prefix.len(), key, self.term)
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: T) -> Result<Option<T>, KeyContainsTerminator> {
+        if !key.contains(&self.term) {
+            Ok(self.insert_impl(key, value))
+        } else {
+            Err(KeyContainsTerminator)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The key must not contain the trie's terminator.
+    pub unsafe fn insert_unchecked(&mut self, key: &[u8], value: T) -> Option<T> {
+        self.insert_impl(key, value)
+    }
+
+    fn insert_impl(&mut self, key: &[u8], value: T) -> Option<T> {
+        let key = [&self.prefix[..], key].concat();
+        let replaced = self.root.insert(&key, value, self.term);
+        if replaced.is_none() {
+            *self.len += 1;
+            #[cfg(feature = "counts")]
+            for count in &mut self.counts {
+                **count += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Count the entries in the view, see
+    /// [counting entries](https://github.com/jmcomets/artsy#counting-entries-under-a-prefix) for the cost.
+    pub fn len(&self) -> usize {
+        self.root.count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> Iter<'a, '_, T> {
+        Iter::new(Leaves::new(Some(&*self.root), Vec::new(), self.term).skip(self.prefix.len()))
+    }
+}
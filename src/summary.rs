@@ -0,0 +1,16 @@
+/// A summary of values, cached by each inner node for the entries below it.
+///
+/// Summaries are combined in key order, so `combine` must be associative, but needn't be
+/// commutative, and `Default` must give its identity (the summary of no entries).
+pub trait Summary<T>: Default {
+    fn from_value(value: &T) -> Self;
+
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// No summary at all, which costs nothing to keep.
+impl<T> Summary<T> for () {
+    fn from_value(_: &T) -> Self {}
+
+    fn combine(&self, _: &Self) -> Self {}
+}